@@ -0,0 +1,162 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use toml;
+
+use keys::KeyBindings;
+use theme::Theme;
+
+/// What the space bar does in edit mode.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceAction {
+    /// Switch between across and down.
+    ToggleDirection,
+    /// Move to the next cell without changing it.
+    SkipCell,
+}
+
+/// How the cell being edited is marked.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorStyle {
+    /// A red arrow pointing in the direction of entry.
+    Arrow,
+    /// The whole cell in reverse video.
+    Highlight,
+    /// The letter underlined.
+    Underline,
+    /// A blinking arrow.
+    Blink,
+}
+
+/// Where the cursor goes after the last letter of a word is entered.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PostWordFlow {
+    /// Keep going in the same direction.
+    NextWord,
+    /// Switch to the word crossing the last cell that was filled.
+    Crossing,
+}
+
+/// Player preferences, read from `~/.config/puzterm/config.toml`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub wrap_navigation: bool,
+    pub space_action: SpaceAction,
+    pub cursor_style: CursorStyle,
+    pub post_word_flow: PostWordFlow,
+    /// Skip over cells that already have a letter while typing.
+    pub skip_filled: bool,
+    /// Jump to the next unfilled clue once a word is full.
+    pub auto_advance: bool,
+    /// Arrow keys carry on into the next word instead of stopping at the end
+    /// of one.
+    pub continue_words: bool,
+    /// Seconds added to the timer each time the grid is checked.
+    pub check_penalty_secs: u64,
+    /// Seconds added to the timer each time something is revealed.
+    pub reveal_penalty_secs: u64,
+    /// Colors, which come from the file given with `--theme` rather than
+    /// from here.
+    #[serde(skip)]
+    pub theme: Theme,
+    /// Key bindings, which come from keys.toml.
+    #[serde(skip)]
+    pub keys: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            wrap_navigation: true,
+            space_action: SpaceAction::ToggleDirection,
+            cursor_style: CursorStyle::Arrow,
+            post_word_flow: PostWordFlow::NextWord,
+            skip_filled: false,
+            auto_advance: false,
+            continue_words: false,
+            check_penalty_secs: 0,
+            reveal_penalty_secs: 0,
+            theme: Theme::default(),
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/puzterm`, falling back to `~/.config/puzterm`.
+pub fn config_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+    .map(|dir| dir.join("puzterm"))
+}
+
+/// `$XDG_DATA_HOME/puzterm`, falling back to `~/.local/share/puzterm`.
+pub fn data_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")),
+    }
+    .map(|dir| dir.join("puzterm"))
+}
+
+impl Config {
+    /// Load the config file, or the defaults if there isn't one.
+    pub fn load() -> Result<Config, String> {
+        let path = match config_dir() {
+            Some(dir) => dir.join("config.toml"),
+            None => return Ok(Config::default()),
+        };
+
+        let mut s = String::new();
+
+        match File::open(&path) {
+            Ok(mut f) => f
+                .read_to_string(&mut s)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        Config::parse(&s).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(s: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        let c = Config::parse("").unwrap();
+
+        assert!(c.wrap_navigation);
+        assert_eq!(c.space_action, SpaceAction::ToggleDirection);
+        assert_eq!(c.cursor_style, CursorStyle::Arrow);
+        assert_eq!(c.post_word_flow, PostWordFlow::NextWord);
+        assert!(!c.skip_filled);
+        assert!(!c.auto_advance);
+        assert!(!c.continue_words);
+        assert_eq!(c.check_penalty_secs, 0);
+        assert_eq!(c.reveal_penalty_secs, 0);
+    }
+
+    #[test]
+    fn space_action() {
+        let c = Config::parse("space_action = \"skip_cell\"").unwrap();
+
+        assert_eq!(c.space_action, SpaceAction::SkipCell);
+        assert!(Config::parse("space_action = \"jump\"").is_err());
+    }
+}
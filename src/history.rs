@@ -0,0 +1,50 @@
+/// A single change to the guess in one cell, and whether it was pencilled in
+/// before and after.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub x: u16,
+    pub y: u16,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub old_pencil: bool,
+    pub new_pencil: bool,
+}
+
+/// Edits that can be undone, and undone edits that can be redone.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    /// Record a new edit. Anything that was undone can no longer be redone.
+    pub fn push(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// The most recent edit, which should now be reverted by restoring `old`.
+    pub fn undo(&mut self) -> Option<Edit> {
+        let edit = self.undo.pop()?;
+        self.redo.push(edit.clone());
+
+        Some(edit)
+    }
+
+    /// The most recently undone edit, which should now be reapplied by
+    /// restoring `new`.
+    pub fn redo(&mut self) -> Option<Edit> {
+        let edit = self.redo.pop()?;
+        self.undo.push(edit.clone());
+
+        Some(edit)
+    }
+
+    /// Drop every edit to a cell, for when its answer has been revealed and
+    /// undoing shouldn't take it away again.
+    pub fn forget(&mut self, x: u16, y: u16) {
+        self.undo.retain(|e| (e.x, e.y) != (x, y));
+        self.redo.retain(|e| (e.x, e.y) != (x, y));
+    }
+}
@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use termion::event::Key;
+use toml;
+
+use config::config_dir;
+
+/// Something a key can be bound to.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveRight,
+    ScrollUp,
+    ScrollDown,
+    /// Start typing into the cell under the cursor.
+    EditMode,
+    /// Stop typing and go back to moving around.
+    NormalMode,
+    Pause,
+    /// Whatever `space_action` in config.toml says.
+    Space,
+    /// Clear the cell, or the one before it if it's already empty.
+    Back,
+    Erase,
+    Rebus,
+    NextClue,
+    PrevClue,
+    FirstUnsolved,
+    NextEmpty,
+    NextPencil,
+    GotoClue,
+    MarkBlack,
+    RevealStructure,
+    Undo,
+    Redo,
+    TogglePencil,
+    ToggleErrorCount,
+    ToggleCheckAsYouType,
+    ToggleWrap,
+    ToggleSkipFilled,
+    ToggleAutoAdvance,
+    ToggleRuler,
+    ToggleMono,
+    ToggleSameLetter,
+    ToggleVerboseStatus,
+    ToggleOverview,
+    ToggleNumbers,
+    Save,
+    CheckLetter,
+    CheckWord,
+    CheckPuzzle,
+    RevealLetter,
+    /// Reveal the first letter of the word being edited.
+    RevealFirstLetter,
+    RevealWord,
+    RevealPuzzle,
+    /// End the game, with `--no-gameover`.
+    Finish,
+    Help,
+    /// Show the notes that came with the puzzle.
+    Notes,
+}
+
+impl Action {
+    /// A few words on what the action does, for the help screen.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move left",
+            Action::MoveDown => "move down",
+            Action::MoveUp => "move up",
+            Action::MoveRight => "move right",
+            Action::ScrollUp => "scroll clues up",
+            Action::ScrollDown => "scroll clues down",
+            Action::EditMode => "edit mode",
+            Action::NormalMode => "normal mode",
+            Action::Pause => "pause / quit",
+            Action::Space => "direction or skip",
+            Action::Back => "erase backwards",
+            Action::Erase => "erase",
+            Action::Rebus => "rebus",
+            Action::NextClue => "next clue",
+            Action::PrevClue => "previous clue",
+            Action::FirstUnsolved => "first unsolved clue",
+            Action::NextEmpty => "next empty square",
+            Action::NextPencil => "next pencil square",
+            Action::GotoClue => "go to a clue",
+            Action::MarkBlack => "mark a black square",
+            Action::RevealStructure => "show black squares",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::TogglePencil => "toggle pencil",
+            Action::ToggleErrorCount => "toggle error count",
+            Action::ToggleCheckAsYouType => "toggle instant check",
+            Action::ToggleWrap => "toggle wrap",
+            Action::ToggleSkipFilled => "toggle skip filled",
+            Action::ToggleAutoAdvance => "toggle auto-advance",
+            Action::ToggleRuler => "toggle ruler",
+            Action::ToggleMono => "toggle colors",
+            Action::ToggleSameLetter => "toggle same letters",
+            Action::ToggleVerboseStatus => "toggle details",
+            Action::ToggleOverview => "toggle overview",
+            Action::ToggleNumbers => "toggle clue numbers",
+            Action::Save => "save",
+            Action::CheckLetter => "check letter",
+            Action::CheckWord => "check word",
+            Action::CheckPuzzle => "check puzzle",
+            Action::RevealLetter => "reveal letter",
+            Action::RevealFirstLetter => "reveal first letter",
+            Action::RevealWord => "reveal word",
+            Action::RevealPuzzle => "reveal puzzle",
+            Action::Finish => "finish",
+            Action::Help => "help",
+            Action::Notes => "show notes",
+        }
+    }
+}
+
+type Defaults = [(Action, &'static [&'static str])];
+
+const NORMAL: &Defaults = &[
+    (Action::ScrollUp, &["pgup", "["]),
+    (Action::ScrollDown, &["pgdown", "]"]),
+    (Action::MoveLeft, &["h", "a", "left"]),
+    (Action::MoveDown, &["j", "s", "down"]),
+    (Action::MoveUp, &["k", "w", "up"]),
+    (Action::MoveRight, &["l", "d", "right"]),
+    (Action::Pause, &["q", "p", "ctrl-c", "esc"]),
+    (Action::ToggleErrorCount, &["e"]),
+    (Action::ToggleCheckAsYouType, &["E"]),
+    (Action::ToggleWrap, &["W"]),
+    (Action::ToggleSkipFilled, &["S"]),
+    (Action::ToggleAutoAdvance, &["A"]),
+    (Action::ToggleRuler, &["r"]),
+    (Action::ToggleMono, &["M"]),
+    (Action::ToggleSameLetter, &["L"]),
+    (Action::ToggleVerboseStatus, &["v"]),
+    (Action::ToggleOverview, &["O"]),
+    (Action::ToggleNumbers, &["#"]),
+    (Action::RevealStructure, &["B"]),
+    (Action::MarkBlack, &["."]),
+    (Action::FirstUnsolved, &["f"]),
+    (Action::NextEmpty, &["n"]),
+    (Action::NextPencil, &["N"]),
+    (Action::GotoClue, &["g"]),
+    (Action::Undo, &["u"]),
+    (Action::TogglePencil, &["P"]),
+    (Action::Redo, &["U"]),
+    (Action::Save, &["ctrl-s"]),
+    (Action::CheckLetter, &["c"]),
+    (Action::CheckWord, &["C"]),
+    (Action::CheckPuzzle, &["K"]),
+    (Action::RevealLetter, &["ctrl-r"]),
+    (Action::RevealFirstLetter, &["ctrl-g"]),
+    (Action::RevealWord, &["ctrl-e"]),
+    (Action::RevealPuzzle, &["ctrl-x"]),
+    (Action::Finish, &["G"]),
+    (Action::EditMode, &["enter", "i"]),
+    (Action::Notes, &["o"]),
+    (Action::Help, &["?"]),
+];
+
+const EDIT: &Defaults = &[
+    (Action::Rebus, &["insert"]),
+    (Action::Erase, &["delete"]),
+    (Action::ScrollUp, &["pgup"]),
+    (Action::ScrollDown, &["pgdown"]),
+    (Action::Back, &["backspace"]),
+    (Action::MoveLeft, &["left"]),
+    (Action::MoveDown, &["down"]),
+    (Action::MoveUp, &["up"]),
+    (Action::MoveRight, &["right"]),
+    (Action::FirstUnsolved, &["ctrl-f"]),
+    (Action::NextEmpty, &["ctrl-n"]),
+    (Action::NextPencil, &["ctrl-t"]),
+    (Action::NextClue, &["tab"]),
+    (Action::PrevClue, &["shift-tab"]),
+    (Action::MarkBlack, &["."]),
+    (Action::Undo, &["ctrl-z"]),
+    (Action::TogglePencil, &["ctrl-p"]),
+    (Action::Redo, &["ctrl-y"]),
+    (Action::Save, &["ctrl-s"]),
+    (Action::CheckLetter, &["ctrl-k"]),
+    (Action::CheckWord, &["ctrl-w"]),
+    (Action::RevealLetter, &["ctrl-r"]),
+    (Action::RevealFirstLetter, &["ctrl-g"]),
+    (Action::RevealWord, &["ctrl-e"]),
+    (Action::NormalMode, &["enter", "esc"]),
+    (Action::Space, &["space"]),
+    (Action::Notes, &["ctrl-o"]),
+    (Action::Help, &["?"]),
+];
+
+/// Keyboard layouts whose letters for moving around sit where h, j, k and l
+/// do on QWERTY, for `--layout`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    Qwerty,
+    Dvorak,
+    Colemak,
+}
+
+/// Normal mode bindings that differ from the usual ones on Dvorak. n moves
+/// right, so finding the next empty square moves to j.
+const DVORAK: &Defaults = &[
+    (Action::MoveLeft, &["d", "left"]),
+    (Action::MoveDown, &["h", "down"]),
+    (Action::MoveUp, &["t", "up"]),
+    (Action::MoveRight, &["n", "right"]),
+    (Action::NextEmpty, &["j"]),
+];
+
+/// Normal mode bindings that differ from the usual ones on Colemak. The
+/// actions moved out of the way go to the QWERTY movement keys left over.
+const COLEMAK: &Defaults = &[
+    (Action::MoveLeft, &["h", "left"]),
+    (Action::MoveDown, &["n", "down"]),
+    (Action::MoveUp, &["e", "up"]),
+    (Action::MoveRight, &["i", "right"]),
+    (Action::NextEmpty, &["j"]),
+    (Action::ToggleErrorCount, &["k"]),
+    (Action::EditMode, &["enter", "l"]),
+];
+
+impl Layout {
+    pub fn parse(s: &str) -> Option<Layout> {
+        match s {
+            "qwerty" => Some(Layout::Qwerty),
+            "dvorak" => Some(Layout::Dvorak),
+            "colemak" => Some(Layout::Colemak),
+            _ => None,
+        }
+    }
+
+    fn normal(self) -> &'static Defaults {
+        match self {
+            Layout::Qwerty => &[],
+            Layout::Dvorak => DVORAK,
+            Layout::Colemak => COLEMAK,
+        }
+    }
+}
+
+/// Read a key written like `"a"`, `"ctrl-s"`, `"alt-x"`, `"pgup"` or `"f5"`.
+pub fn parse_key(s: &str) -> Result<Key, String> {
+    let one = |s: &str| {
+        let mut chars = s.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    if let Some(c) = one(s) {
+        return Ok(Key::Char(c));
+    }
+
+    let lower = s.to_lowercase();
+
+    if let Some(c) = lower.strip_prefix("ctrl-").and_then(one) {
+        return Ok(Key::Ctrl(c));
+    }
+
+    if let Some(c) = s
+        .get(4..)
+        .filter(|_| lower.starts_with("alt-"))
+        .and_then(one)
+    {
+        return Ok(Key::Alt(c));
+    }
+
+    if let Some(Ok(n)) = lower.strip_prefix('f').map(str::parse) {
+        if (1..=12).contains(&n) {
+            return Ok(Key::F(n));
+        }
+    }
+
+    match &lower[..] {
+        "left" => Ok(Key::Left),
+        "right" => Ok(Key::Right),
+        "up" => Ok(Key::Up),
+        "down" => Ok(Key::Down),
+        "home" => Ok(Key::Home),
+        "end" => Ok(Key::End),
+        "pgup" => Ok(Key::PageUp),
+        "pgdown" => Ok(Key::PageDown),
+        "backspace" => Ok(Key::Backspace),
+        "delete" => Ok(Key::Delete),
+        "insert" => Ok(Key::Insert),
+        "enter" => Ok(Key::Char('\n')),
+        "tab" => Ok(Key::Char('\t')),
+        "shift-tab" => Ok(Key::BackTab),
+        "space" => Ok(Key::Char(' ')),
+        "esc" => Ok(Key::Esc),
+        _ => Err(format!("unknown key: {:?}", s)),
+    }
+}
+
+/// A key the way it's written in keys.toml.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char('\n') => "enter".into(),
+        Key::Char('\t') => "tab".into(),
+        Key::Char(' ') => "space".into(),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("ctrl-{}", c),
+        Key::Alt(c) => format!("alt-{}", c),
+        Key::F(n) => format!("f{}", n),
+        Key::Left => "left".into(),
+        Key::Right => "right".into(),
+        Key::Up => "up".into(),
+        Key::Down => "down".into(),
+        Key::Home => "home".into(),
+        Key::End => "end".into(),
+        Key::PageUp => "pgup".into(),
+        Key::PageDown => "pgdown".into(),
+        Key::Backspace => "backspace".into(),
+        Key::Delete => "delete".into(),
+        Key::Insert => "insert".into(),
+        Key::BackTab => "shift-tab".into(),
+        Key::Esc => "esc".into(),
+        k => format!("{:?}", k),
+    }
+}
+
+/// The keys bound to each action in `map`, going through the actions in the
+/// order of `defaults`. Keys that are bound by default come first, in their
+/// usual order.
+fn describe(map: &HashMap<Key, Action>, defaults: &Defaults) -> Vec<(String, &'static str)> {
+    defaults
+        .iter()
+        .filter_map(|&(action, usual)| {
+            let mut keys: Vec<String> = map
+                .iter()
+                .filter(|&(_, &a)| a == action)
+                .map(|(&k, _)| key_name(k))
+                .collect();
+
+            if keys.is_empty() {
+                return None;
+            }
+
+            keys.sort_by_key(|k| {
+                let i = usual.iter().position(|u| u == k);
+                (i.unwrap_or(usual.len()), k.clone())
+            });
+
+            Some((keys.join(" "), action.description()))
+        })
+        .collect()
+}
+
+/// Keys for each action, as written in keys.toml. The actions are plain
+/// strings here since toml can't read table keys as enums.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeysFile {
+    normal: HashMap<String, Vec<String>>,
+    edit: HashMap<String, Vec<String>>,
+}
+
+/// What each key does in normal and edit mode, read from
+/// `~/.config/puzterm/keys.toml`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    pub normal: HashMap<Key, Action>,
+    pub edit: HashMap<Key, Action>,
+}
+
+/// Give each action listed in `defaults` those keys, instead of any it had.
+fn bind(map: &mut HashMap<Key, Action>, defaults: &Defaults) {
+    for &(action, keys) in defaults {
+        map.retain(|_, &mut a| a != action);
+
+        for key in keys {
+            map.insert(parse_key(key).unwrap(), action);
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        let mut keys = KeyBindings {
+            normal: HashMap::new(),
+            edit: HashMap::new(),
+        };
+
+        bind(&mut keys.normal, NORMAL);
+        bind(&mut keys.edit, EDIT);
+
+        keys
+    }
+}
+
+/// Give each action listed in `changes` the keys listed for it, instead of
+/// its usual ones. Taking a key another action still uses is an error, rather
+/// than quietly leaving that action without it.
+fn rebind(
+    map: &mut HashMap<Key, Action>,
+    changes: HashMap<String, Vec<String>>,
+) -> Result<(), String> {
+    let mut actions = Vec::new();
+
+    for (name, keys) in changes {
+        let action = Action::deserialize(name.into_deserializer())
+            .map_err(|e: value::Error| e.to_string())?;
+
+        map.retain(|_, &mut a| a != action);
+        actions.push((action, keys));
+    }
+
+    for (action, keys) in actions {
+        for key in keys {
+            let k = parse_key(&key)?;
+
+            match map.insert(k, action) {
+                Some(other) if other != action => {
+                    return Err(format!(
+                        "{} is already bound to {}",
+                        key,
+                        other.description()
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl KeyBindings {
+    /// Load the key bindings file, or the defaults for `layout` if there
+    /// isn't one.
+    pub fn load(layout: Layout) -> Result<KeyBindings, String> {
+        let path = match config_dir() {
+            Some(dir) => dir.join("keys.toml"),
+            None => return KeyBindings::parse("", layout),
+        };
+
+        let mut s = String::new();
+
+        match File::open(&path) {
+            Ok(mut f) => f
+                .read_to_string(&mut s)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return KeyBindings::parse("", layout)
+            }
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        KeyBindings::parse(&s, layout).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// What every key does, with normal mode on the left and edit mode on the
+    /// right. All the lines are the same width.
+    pub fn help(&self) -> Vec<String> {
+        let normal = describe(&self.normal, NORMAL);
+        let edit = describe(&self.edit, EDIT);
+
+        let keys_width =
+            |rows: &[(String, &str)]| rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+        let column = |rows: &[(String, &str)]| -> Vec<String> {
+            let width = keys_width(rows);
+
+            rows.iter()
+                .map(|&(ref keys, description)| {
+                    format!("{:>w$}  {:<20}", keys, description, w = width)
+                })
+                .collect()
+        };
+
+        let mut left = column(&normal);
+        let mut right = column(&edit);
+
+        let left_width = keys_width(&normal) + 22;
+        let right_width = keys_width(&edit) + 22;
+
+        left.insert(0, format!("{:<w$}", "Normal mode", w = left_width));
+        right.insert(0, format!("{:<w$}", "Edit mode", w = right_width));
+        left.insert(1, " ".repeat(left_width));
+        right.insert(1, " ".repeat(right_width));
+
+        let rows = left.len().max(right.len());
+        left.resize(rows, " ".repeat(left_width));
+        right.resize(rows, " ".repeat(right_width));
+
+        left.into_iter()
+            .zip(right)
+            .map(|(l, r)| format!("{}    {}", l, r))
+            .collect()
+    }
+
+    /// The default bindings for `layout`, with the actions in `s` moved to
+    /// other keys.
+    pub fn parse(s: &str, layout: Layout) -> Result<KeyBindings, String> {
+        let file: KeysFile = toml::from_str(s).map_err(|e| e.to_string())?;
+        let mut keys = KeyBindings::default();
+
+        bind(&mut keys.normal, layout.normal());
+
+        rebind(&mut keys.normal, file.normal)?;
+        rebind(&mut keys.edit, file.edit)?;
+
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        assert_eq!(parse_key("a"), Ok(Key::Char('a')));
+        assert_eq!(parse_key("G"), Ok(Key::Char('G')));
+        assert_eq!(parse_key("Ctrl-S"), Ok(Key::Ctrl('s')));
+        assert_eq!(parse_key("alt-X"), Ok(Key::Alt('X')));
+        assert_eq!(parse_key("f5"), Ok(Key::F(5)));
+        assert_eq!(parse_key("f"), Ok(Key::Char('f')));
+        assert_eq!(parse_key("shift-tab"), Ok(Key::BackTab));
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("hyper-q").is_err());
+    }
+
+    #[test]
+    fn key_names() {
+        for &(_, keys) in NORMAL.iter().chain(EDIT) {
+            for &k in keys {
+                assert_eq!(key_name(parse_key(k).unwrap()), k);
+            }
+        }
+    }
+
+    #[test]
+    fn help() {
+        let k = KeyBindings::parse(
+            "[normal]\nmove_left = [\"left\", \"x\", \"h\"]",
+            Layout::Qwerty,
+        )
+        .unwrap();
+        let help = k.help();
+
+        assert!(help[0].starts_with("Normal mode"));
+        assert!(help.iter().all(|l| l.len() == help[0].len()));
+        assert!(help.iter().any(|l| l.contains("h left x  move left")));
+        assert!(help.iter().any(|l| l.contains("shift-tab  previous clue")));
+    }
+
+    #[test]
+    fn rebinding() {
+        assert_eq!(
+            KeyBindings::parse("", Layout::Qwerty).unwrap(),
+            KeyBindings::default()
+        );
+
+        let k = KeyBindings::parse(
+            "[normal]\nmove_left = [\"left\"]\ncheck_word = [\"x\"]\n[edit]\nnext_clue = [\"ctrl-l\"]",
+            Layout::Qwerty,
+        )
+        .unwrap();
+
+        assert_eq!(k.normal.get(&Key::Char('h')), None);
+        assert_eq!(k.normal.get(&Key::Left), Some(&Action::MoveLeft));
+        assert_eq!(k.normal.get(&Key::Char('x')), Some(&Action::CheckWord));
+        assert_eq!(k.normal.get(&Key::Char('C')), None);
+        assert_eq!(k.normal.get(&Key::Char('j')), Some(&Action::MoveDown));
+        assert_eq!(k.edit.get(&Key::Ctrl('l')), Some(&Action::NextClue));
+        assert_eq!(k.edit.get(&Key::Char('\t')), None);
+
+        // Keys can only be taken from actions that are given others.
+
+        assert_eq!(
+            KeyBindings::parse("[edit]\nnext_clue = [\"ctrl-n\"]", Layout::Qwerty),
+            Err("ctrl-n is already bound to next empty square".into())
+        );
+        assert!(
+            KeyBindings::parse("[normal]\nundo = [\"x\"]\nredo = [\"x\"]", Layout::Qwerty).is_err()
+        );
+
+        let k = KeyBindings::parse(
+            "[edit]\nnext_clue = [\"ctrl-n\"]\nnext_empty = [\"ctrl-b\"]",
+            Layout::Qwerty,
+        )
+        .unwrap();
+
+        assert_eq!(k.edit.get(&Key::Ctrl('n')), Some(&Action::NextClue));
+
+        assert!(KeyBindings::parse("[normal]\nfly = [\"x\"]", Layout::Qwerty).is_err());
+        assert!(KeyBindings::parse("[normal]\nundo = [\"nope\"]", Layout::Qwerty).is_err());
+    }
+
+    #[test]
+    fn layouts() {
+        assert_eq!(Layout::parse("dvorak"), Some(Layout::Dvorak));
+        assert_eq!(Layout::parse("azerty"), None);
+
+        let k = KeyBindings::parse("", Layout::Dvorak).unwrap();
+
+        assert_eq!(k.normal.get(&Key::Char('d')), Some(&Action::MoveLeft));
+        assert_eq!(k.normal.get(&Key::Char('n')), Some(&Action::MoveRight));
+        assert_eq!(k.normal.get(&Key::Char('j')), Some(&Action::NextEmpty));
+        assert_eq!(k.normal.get(&Key::Char('l')), None);
+        assert_eq!(k.normal.get(&Key::Left), Some(&Action::MoveLeft));
+        assert_eq!(k.edit, KeyBindings::default().edit);
+
+        // The bindings file still has the last word.
+
+        let k = KeyBindings::parse("[normal]\nmove_up = [\"up\", \"y\"]", Layout::Colemak).unwrap();
+
+        assert_eq!(k.normal.get(&Key::Char('y')), Some(&Action::MoveUp));
+        assert_eq!(k.normal.get(&Key::Char('e')), None);
+        assert_eq!(k.normal.get(&Key::Char('i')), Some(&Action::MoveRight));
+        assert_eq!(k.normal.get(&Key::Char('l')), Some(&Action::EditMode));
+        assert_eq!(k.normal.get(&Key::Char('\n')), Some(&Action::EditMode));
+    }
+}
@@ -0,0 +1,13 @@
+//! Reading .puz files and the New York Times' JSON, and drawing grids as
+//! text or SVG, for programs other than the terminal game.
+
+#[macro_use]
+extern crate nom;
+extern crate encoding;
+extern crate serde;
+extern crate serde_json;
+
+pub mod nyt;
+pub mod puzfile;
+pub mod render;
+pub mod svg;
@@ -1,35 +1,64 @@
-#[macro_use]
-extern crate nom;
-extern crate encoding;
+extern crate puzterm;
+extern crate serde;
+extern crate serde_json;
 extern crate stopwatch;
 extern crate termion;
+extern crate toml;
+extern crate unicode_width;
+extern crate zip;
 
+use std::collections::HashMap;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Read, Write};
-use std::path::Path;
-use std::time::Duration;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use termion::event::Key;
-use termion::input::TermRead;
+use termion::event::{Event, Key, MouseButton, MouseEvent};
+use termion::input::{MouseTerminal, TermRead};
 use termion::raw::IntoRawMode;
 use termion::{async_stdin, clear, color, cursor, style};
 
 use stopwatch::Stopwatch;
 
-use nom::Err;
-
-mod puzfile;
-
-use puzfile::PuzFile;
-
-#[derive(Copy, Clone)]
+mod config;
+mod history;
+mod keys;
+mod menu;
+mod options;
+mod pack;
+mod records;
+mod sidecar;
+mod text;
+mod theme;
+
+use config::{Config, CursorStyle, PostWordFlow, SpaceAction};
+use history::{Edit, History};
+use keys::{Action, KeyBindings};
+use options::{Format, Options};
+use puzterm::puzfile::{self, PuzFile};
+use puzterm::{nyt, render, svg};
+use records::Records;
+use sidecar::Sidecar;
+use theme::Theme;
+
+/// How long a message stays in the status bar.
+const MESSAGE_TIME: Duration = Duration::from_secs(3);
+
+/// The most columns the clue list takes when the game is centered, enough for
+/// Across and Down side by side.
+const CENTERED_CLUES_WIDTH: u16 = 80;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 enum Mode {
     EditAcross,
     EditDown,
     GameOver,
     Select,
     Pause,
+    /// The title screen shown before the clock starts.
+    Intro,
 }
 
 #[derive(Copy, Clone)]
@@ -40,128 +69,642 @@ enum Direction {
     Up,
 }
 
+/// A screen covering the grid until it's dismissed.
+#[derive(Copy, Clone, PartialEq)]
+enum Overlay {
+    /// The list of keys.
+    Help,
+    /// The puzzle's notes, scrolled down this many lines.
+    Notes(usize),
+}
+
+/// What a prompt in the status bar is asking for.
+#[derive(Copy, Clone, PartialEq)]
+enum Prompt {
+    /// Several letters for the current cell.
+    Rebus,
+    /// A clue number, optionally followed by A or D.
+    GotoClue,
+}
+
 #[derive(Debug)]
 pub struct Cell {
-    truth: Option<char>,
-    guess: Option<char>,
+    /// The answer, which is more than one letter in a rebus cell.
+    truth: Option<String>,
+    guess: Option<String>,
     clue_number: Option<u16>,
     clue_across: Option<String>,
     clue_down: Option<String>,
+    marked_wrong: bool,
+    revealed: bool,
+    circled: bool,
+    /// Marked black by the player, in a diagramless puzzle.
+    user_black: bool,
+    /// The guess was entered in pencil because the player isn't sure of it.
+    pencil: bool,
 }
 
+/// The first cell of an across or down word.
+#[derive(Copy, Clone, Debug)]
+struct Word {
+    x: u16,
+    y: u16,
+    across: bool,
+}
+
+/// A line of the clue list, and the word its clue is for.
+type ClueLine = (Option<Word>, String);
+
 pub struct Game<R, W: Write> {
     width: u16,
     height: u16,
     grid: Vec<Cell>,
+    words: Vec<Word>,
     cursor_x: u16,
     cursor_y: u16,
     clues_scroll: u16,
+    /// Where the down column is scrolled to, when the clues are shown side by
+    /// side. Otherwise `clues_scroll` covers both.
+    down_clues_scroll: u16,
     mode: Mode,
     last_edit_mode: Mode,
     stdout: W,
     stdin: R,
     stopwatch: Stopwatch,
-    tick: u64,
+    /// When a key or mouse event last came in.
+    last_input: Instant,
+    /// The second the clock showed when the status bar was last redrawn.
+    shown_secs: Option<u64>,
     version: &'static str,
     hint_num_errors: bool,
+    /// Show wrong letters in the error color as soon as they're typed.
+    check_as_you_type: bool,
+    /// Time added to the clock for checking and revealing.
+    penalty: Duration,
+    /// Time spent on the puzzle in earlier sessions.
+    earlier: Duration,
+    /// Time spent on the pause screen, which doesn't count towards the clock.
+    paused: Duration,
+    /// When the game was paused, if it still is.
+    paused_since: Option<Instant>,
+    checks: u32,
+    reveals: u32,
+    config: Config,
+    theme: Theme,
+    /// No colors, just bold and reverse video.
+    mono: bool,
+    /// Terminal columns and rows taken by each cell, including its right and
+    /// bottom borders.
+    cell_width: u16,
+    cell_height: u16,
+    /// Draw the grid one character per cell, to see all of a big one at once.
+    overview: bool,
+    /// Center the grid and clues in the terminal instead of starting at the
+    /// left edge.
+    center: bool,
+    /// Columns left empty to the left of the grid when centering.
+    margin: u16,
+    /// The leftmost column of cells on screen, when the grid is too wide to
+    /// show all at once.
+    grid_scroll_x: u16,
+    /// The top row of cells on screen, when the grid is too tall to show all
+    /// at once.
+    grid_scroll_y: u16,
+    /// How many columns of cells fit on screen.
+    grid_cols: u16,
+    /// How many rows of cells fit on screen.
+    grid_rows: u16,
+    no_gameover: bool,
+    wrap_navigation: bool,
+    /// Jump past letters that are already filled in while typing.
+    skip_filled: bool,
+    /// Move on to the next unfilled clue when a word is finished.
+    auto_advance: bool,
+    show_ruler: bool,
+    /// Show everything in a multi-line status area instead of a single bar.
+    verbose_status: bool,
+    hide_blocks: bool,
+    structure_revealed: bool,
+    message: Option<String>,
+    message_time: Instant,
+    from_menu: bool,
+    back_to_menu: bool,
+    sidecar_path: Option<PathBuf>,
+    /// Where solved puzzles are recorded, if anywhere.
+    records_path: Option<PathBuf>,
+    /// The .puz file that progress is saved back into.
+    puz_path: Option<PathBuf>,
+    /// The state section as of the last save, so unchanged progress isn't
+    /// saved again.
+    saved_state: String,
+    /// The timer as of the last save, in seconds.
+    saved_secs: u64,
+    /// Terminal size as of the last full redraw.
+    term_size: (u16, u16),
+    /// The grid doesn't fit in the terminal, so play is on hold.
+    too_small: bool,
+    /// The pause screen is asking whether to start the puzzle over.
+    confirm_reset: bool,
+    /// Whatever is covering the grid, if anything.
+    overlay: Option<Overlay>,
+    /// Guesses typed or erased by the player, for undo and redo.
+    history: History,
+    /// Letters typed now are tentative.
+    pencil_mode: bool,
+    autosave_secs: u64,
+    /// The key that unlocked the puzzle, if it was scrambled.
+    key: Option<u16>,
+    /// Time on the stopwatch when progress was last autosaved.
+    autosave_time: Duration,
+    checksum: u16,
+    record_key: records::Key,
     title: String,
     author: String,
+    copyright: String,
+    notes: String,
+    /// Problems with the puzzle file that could be worked around, shown after
+    /// the notes.
+    warnings: Vec<String>,
+    /// Start out editing the first across clue once the intro is over.
+    start_solving: bool,
+    /// The clock has been started by the first move in the grid.
+    started: bool,
+    /// The puzzle was saved with its timer running, so the clock carries on
+    /// as soon as the grid is shown rather than waiting for a move.
+    resume_clock: bool,
+    /// Cells of the word being edited.
+    highlighted: Vec<(u16, u16)>,
+    /// Shade the other cells holding the same letter as the cursor cell.
+    show_same_letter: bool,
+    /// Show clue numbers in the corners of cells.
+    show_numbers: bool,
+    /// Cells shaded because they hold the same letter as the cursor cell.
+    same_letter: Vec<(u16, u16)>,
+    /// Where the cursor's cell was last drawn in reverse video.
+    drawn_cursor: Option<(u16, u16)>,
+    /// Colors to draw guesses in instead of the usual ones, while a cell is
+    /// flashed.
+    color_override: Option<String>,
+    /// The prompt open in the status bar, and what's been typed so far.
+    prompt: Option<(Prompt, String)>,
+    /// Where each numbered clue starts.
+    clue_cells: HashMap<u16, (u16, u16)>,
+    /// Completion last shown in the terminal window title, if it was set.
+    title_percent: Option<u16>,
 }
 
 pub struct GameStatus {
     cells: u16,
     guesses: u16,
     errors: u16,
+    /// Cells whose answers were revealed rather than solved.
+    revealed: u16,
+}
+
+/// Play a puzzle. Returns true if the player asked to go back to the menu.
+///
+/// If the puzzle came from a file, check and reveal marks are kept in a
+/// sidecar file next to it between sessions. Progress in a .puz file is saved
+/// back into it on the way out.
+fn init<W: Write, R: Read>(
+    stdin: R,
+    mut stdout: W,
+    p: &PuzFile,
+    config: &Config,
+    options: &Options,
+    path: Option<&Path>,
+    from_menu: bool,
+) -> bool {
+    // The cursor's cell is drawn in reverse video, so the terminal's own
+    // cursor would only be in the way. Dropping the game shows it again.
+
+    write!(stdout, "{}{}", clear::All, cursor::Hide).unwrap();
+
+    let mut g = Game::new(stdin.events(), stdout, p, config);
+    g.no_gameover = options.no_gameover;
+    g.mono = options.mono;
+    g.center = options.center;
+    g.overview = options.overview;
+    g.from_menu = from_menu;
+    g.sidecar_path = path.map(sidecar::path_for);
+    g.records_path = records::path();
+    g.puz_path = path
+        .filter(|_| p.is_puz() && p.missing_clues == 0)
+        .map(Path::to_path_buf);
+    g.autosave_secs = options.autosave_secs;
+    g.key = options.key;
+
+    if options.compact {
+        g.cell_width = 2;
+        g.cell_height = 2;
+    }
+
+    g.load_sidecar();
+    g.saved_state = g.state();
+    g.saved_secs = g.elapsed().as_secs();
+    g.start_solving = options.start_solving;
+    g.intro();
+
+    g.start();
+    g.save_sidecar();
+    g.autosave(true);
+
+    g.back_to_menu
+}
+
+impl<R, W: Write> Drop for Game<R, W> {
+    fn drop(&mut self) {
+        // When done, restore the defaults to avoid messing with the terminal.
+        // Terminals that keep a stack of window titles get the old one back.
+        if self.title_percent.is_some() {
+            write!(self.stdout, "\x1b[23;0t").unwrap();
+        }
+
+        write!(
+            self.stdout,
+            "{}{}{}{}",
+            clear::All,
+            style::Reset,
+            cursor::Goto(1, 1),
+            cursor::Show
+        )
+        .unwrap();
+    }
 }
 
-fn init<W: Write, R: Read>(stdin: R, mut stdout: W, p: &PuzFile) {
-    let mut grid = Vec::new();
+impl<R: Iterator<Item = Result<Event, std::io::Error>>, W: Write> Game<R, W> {
+    fn new(stdin: R, stdout: W, p: &PuzFile, config: &Config) -> Game<R, W> {
+        let mut grid = Vec::new();
+
+        // Pick up where the player left off if the file has their progress in
+        // it.
+
+        let mut state = p.state.chars();
+
+        for (i, c) in p.puzzle.chars().enumerate() {
+            let truth = match c {
+                _ if puzfile::is_black(c) => None,
+                _ => match p.rebus.get(i) {
+                    Some(Some(rebus)) => Some(rebus.clone()),
+                    _ => Some(c.to_string()),
+                },
+            };
+            let guess = match state.next() {
+                Some('-') | Some('.') | None => None,
+                Some(_) if truth.is_none() => None,
+                Some(g) => Some(g.to_uppercase().collect::<String>()),
+            };
+            let markup = p.markup.get(i).cloned().unwrap_or(0);
+
+            grid.push(Cell {
+                marked_wrong: markup & puzfile::MARKUP_INCORRECT != 0
+                    && guess.is_some()
+                    && guess != truth,
+                revealed: markup & puzfile::MARKUP_GIVEN != 0 && guess.is_some() && guess == truth,
+                circled: markup & puzfile::MARKUP_CIRCLED != 0,
+                user_black: false,
+                pencil: false,
+                truth,
+                guess,
+                clue_number: None,
+                clue_across: None, // TODO
+                clue_down: None,   // TODO
+            });
+        }
 
-    for c in p.puzzle.chars() {
-        let truth = match c {
-            '.' => None,
-            _ => Some(c),
+        let mut g = Game {
+            width: u16::from(p.width),
+            height: u16::from(p.height),
+            grid,
+            words: Vec::new(),
+            cursor_x: 0,
+            cursor_y: 0,
+            clues_scroll: 0,
+            down_clues_scroll: 0,
+            mode: Mode::Select,
+            last_edit_mode: Mode::EditAcross,
+            stdout,
+            stdin,
+            stopwatch: Stopwatch::new(),
+            paused: Duration::from_secs(0),
+            paused_since: None,
+            last_input: Instant::now(),
+            shown_secs: None,
+            version: env!("CARGO_PKG_VERSION"),
+            hint_num_errors: false,
+            check_as_you_type: false,
+            penalty: Duration::from_secs(0),
+            earlier: Duration::from_secs(p.timer_elapsed.unwrap_or(0)),
+            checks: 0,
+            reveals: 0,
+            config: config.clone(),
+            theme: config.theme.clone(),
+            mono: false,
+            cell_width: 4,
+            cell_height: 3,
+            overview: false,
+            center: false,
+            margin: 0,
+            grid_scroll_x: 0,
+            grid_scroll_y: 0,
+            grid_cols: u16::from(p.width),
+            grid_rows: u16::from(p.height),
+            no_gameover: false,
+            wrap_navigation: config.wrap_navigation,
+            skip_filled: config.skip_filled,
+            auto_advance: config.auto_advance,
+            show_ruler: false,
+            verbose_status: false,
+            hide_blocks: p.is_diagramless(),
+            structure_revealed: false,
+            message: None,
+            message_time: Instant::now(),
+            from_menu: false,
+            back_to_menu: false,
+            sidecar_path: None,
+            records_path: None,
+            puz_path: None,
+            saved_state: String::new(),
+            saved_secs: 0,
+            term_size: (0, 0),
+            too_small: false,
+            confirm_reset: false,
+            overlay: None,
+            history: History::default(),
+            pencil_mode: false,
+            autosave_secs: 0,
+            key: None,
+            autosave_time: Duration::from_secs(0),
+            checksum: p.solution_checksum(),
+            record_key: records::key(p),
+            title: text::strip_controls(&p.title),
+            author: text::strip_controls(&p.author),
+            copyright: text::strip_controls(&p.copyright),
+            notes: text::strip_controls(&p.notes),
+            warnings: p
+                .unexpected_cells()
+                .iter()
+                .map(|&(c, n)| {
+                    let squares = if n == 1 { "square" } else { "squares" };
+                    format!(
+                        "Unexpected '{}' in {} {} of the solution, shown as black.",
+                        c, n, squares
+                    )
+                })
+                .collect(),
+            start_solving: false,
+            started: false,
+            resume_clock: p.timer_running,
+            highlighted: Vec::new(),
+            show_same_letter: false,
+            show_numbers: true,
+            same_letter: Vec::new(),
+            drawn_cursor: None,
+            color_override: None,
+            prompt: None,
+            clue_cells: HashMap::new(),
+            title_percent: None,
         };
 
-        grid.push(Cell {
-            truth,
-            guess: None,
-            clue_number: None,
-            clue_across: None, // TODO
-            clue_down: None,   // TODO
-        });
+        let mut clue_number = 1;
+        let mut clue_index = 0;
+
+        for y in 0..g.height {
+            for x in 0..g.width {
+                let across = g.has_clue_across(x, y);
+                let down = g.has_clue_down(x, y);
+
+                let c = g.get_mut(x, y);
+
+                if across {
+                    c.clue_across = p.clues.get(clue_index).map(|c| text::strip_controls(c));
+
+                    clue_index += 1;
+                }
+
+                if down {
+                    c.clue_down = p.clues.get(clue_index).map(|c| text::strip_controls(c));
+
+                    clue_index += 1;
+                }
+
+                if across || down {
+                    c.clue_number = Some(clue_number);
+                    g.clue_cells.insert(clue_number, (x, y));
+                }
+
+                if across {
+                    g.words.push(Word { x, y, across: true });
+                }
+
+                if down {
+                    g.words.push(Word {
+                        x,
+                        y,
+                        across: false,
+                    });
+                }
+
+                if across || down {
+                    clue_number += 1;
+                }
+            }
+        }
+
+        g
     }
 
-    write!(stdout, "{}", clear::All).unwrap();
+    /// Restore check and reveal marks, and how much help the player had,
+    /// saved by a previous session.
+    fn load_sidecar(&mut self) {
+        let s = match self.sidecar_path {
+            Some(ref path) => match Sidecar::load(path, self.checksum) {
+                Some(s) => s,
+                None => return,
+            },
+            None => return,
+        };
 
-    let mut g = Game {
-        width: u16::from(p.width),
-        height: u16::from(p.height),
-        grid,
-        cursor_x: 0,
-        cursor_y: 0,
-        clues_scroll: 0,
-        mode: Mode::Select,
-        last_edit_mode: Mode::EditAcross,
-        stdout,
-        stdin: stdin.keys(),
-        stopwatch: Stopwatch::new(),
-        tick: 0,
-        version: env!("CARGO_PKG_VERSION"),
-        hint_num_errors: false,
-        title: p.title.clone(),
-        author: p.author.clone(),
-    };
+        if s.marked_wrong.len() != self.grid.len() || s.revealed.len() != self.grid.len() {
+            return;
+        }
 
-    let mut clue_number = 1;
-    let mut clue_index = 0;
+        self.checks = s.checks;
+        self.reveals = s.reveals;
 
-    for y in 0..g.height {
-        for x in 0..g.width {
-            let across = g.has_clue_across(x, y);
-            let down = g.has_clue_down(x, y);
+        if s.structure_revealed {
+            self.structure_revealed = true;
+            self.hide_blocks = false;
+        }
 
-            let c = g.get_mut(x, y);
+        for (cell, (&wrong, &revealed)) in self
+            .grid
+            .iter_mut()
+            .zip(s.marked_wrong.iter().zip(s.revealed.iter()))
+        {
+            if cell.truth.is_none() {
+                continue;
+            }
 
-            if across {
-                c.clue_across = Some(p.clues[clue_index].clone());
+            // A revealed cell always holds the answer.
 
-                clue_index += 1;
+            if revealed {
+                cell.revealed = true;
+                cell.guess = cell.truth.clone();
             }
 
-            if down {
-                c.clue_down = Some(p.clues[clue_index].clone());
+            // A wrong mark only means something while the wrong guess is
+            // still there.
 
-                clue_index += 1;
+            if wrong && cell.guess.is_some() && cell.guess != cell.truth {
+                cell.marked_wrong = true;
             }
+        }
+    }
+
+    /// The player's grid as the .puz state section: `-` for empty, `.` for
+    /// black. Only the first letter of a rebus fits.
+    fn state(&self) -> String {
+        self.grid
+            .iter()
+            .map(|cell| match (&cell.truth, &cell.guess) {
+                (None, _) => '.',
+                (Some(_), Some(g)) => g.chars().next().unwrap_or('-'),
+                (Some(_), None) => '-',
+            })
+            .collect()
+    }
+
+    /// Write the player's progress into the state section of a .puz file.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut data = fs::read(path)?;
+
+        let mut p = puzfile::parse(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        // The state and timer are found by where the clues end, so writing
+        // them into a file that's missing some would only damage it further.
+
+        if p.missing_clues > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the puzzle file is missing some of its clues",
+            ));
+        }
+
+        // A locked puzzle stays locked in the file, so unlock a copy to check
+        // it's the same one.
+
+        let mut solution = p.clone();
+
+        if let (true, Some(key)) = (solution.is_scrambled(), self.key) {
+            solution.unscramble(key).ok();
+        }
+
+        if solution.solution_checksum() != self.checksum {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the puzzle file has changed",
+            ));
+        }
+
+        p.state = self.state();
+        p.write_state(&mut data);
+        p.write_timer(
+            &mut data,
+            self.elapsed().as_secs(),
+            self.stopwatch.is_running(),
+        );
+
+        fs::write(path, data)
+    }
+
+    /// Quietly save progress if any guess has changed since the last save. On
+    /// the way out, `timer` saves it if only the clock has moved on, too.
+    fn autosave(&mut self, timer: bool) {
+        let state = self.state();
+        let secs = self.elapsed().as_secs();
+
+        if state == self.saved_state && (!timer || secs == self.saved_secs) {
+            return;
+        }
 
-            if across || down {
-                c.clue_number = Some(clue_number);
+        let saved = match self.puz_path {
+            Some(ref path) => self.save(path).is_ok(),
+            None => false,
+        };
+
+        if saved {
+            self.saved_state = state;
+            self.saved_secs = secs;
+        }
+    }
+
+    /// Save progress and say how it went.
+    fn save_progress(&mut self) {
+        let result = match self.puz_path {
+            Some(ref path) => self.save(path),
+            None => {
+                self.show_message("Only .puz files can be saved");
+                return;
+            }
+        };
 
-                clue_number += 1;
+        match result {
+            Ok(()) => {
+                self.saved_state = self.state();
+                self.saved_secs = self.elapsed().as_secs();
+                self.show_message("Saved");
             }
+            Err(e) => self.show_message(&format!("Couldn't save: {}", e)),
         }
     }
 
-    g.draw_all();
-    g.start();
-}
+    /// Save check and reveal marks, and how much help the player had, for the
+    /// next session.
+    fn save_sidecar(&self) {
+        let path = match self.sidecar_path {
+            Some(ref path) => path,
+            None => return,
+        };
 
-impl<R, W: Write> Drop for Game<R, W> {
-    fn drop(&mut self) {
-        // When done, restore the defaults to avoid messing with the terminal.
-        write!(
-            self.stdout,
-            "{}{}{}",
-            clear::All,
-            style::Reset,
-            cursor::Goto(1, 1)
-        )
-        .unwrap();
+        let s = Sidecar {
+            checksum: self.checksum,
+            marked_wrong: self.grid.iter().map(|c| c.marked_wrong).collect(),
+            revealed: self.grid.iter().map(|c| c.revealed).collect(),
+            checks: self.checks,
+            reveals: self.reveals,
+            structure_revealed: self.structure_revealed,
+        };
+
+        // Failing to save these is not worth interrupting the player over.
+
+        if s.is_empty() {
+            fs::remove_file(path).ok();
+        } else {
+            s.save(path).ok();
+        }
+    }
+
+    /// The coordinates of the cell at an index into `grid`.
+    fn cell_at(&self, i: usize) -> (u16, u16) {
+        let width = self.width as usize;
+
+        ((i % width) as u16, (i / width) as u16)
+    }
+
+    /// What's been filled into a cell, for tests.
+    #[cfg(test)]
+    fn cell_guess(&self, x: u16, y: u16) -> Option<&str> {
+        self.get(x, y).guess.as_deref()
+    }
+
+    /// Where the cursor is, for tests.
+    #[cfg(test)]
+    fn cursor(&self) -> (u16, u16) {
+        (self.cursor_x, self.cursor_y)
     }
-}
 
-impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
     fn get(&self, x: u16, y: u16) -> &Cell {
         &self.grid[y as usize * self.width as usize + x as usize]
     }
@@ -211,6 +754,7 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
             cells: 0,
             guesses: 0,
             errors: 0,
+            revealed: 0,
         };
 
         for cell in &self.grid {
@@ -218,11 +762,17 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
                 s.cells += 1;
             }
 
-            if cell.guess.is_some() {
+            // Guesses in black cells of a diagramless puzzle don't count.
+
+            if cell.truth.is_some() && cell.guess.is_some() {
                 s.guesses += 1;
             }
 
-            match (cell.truth, cell.guess) {
+            if cell.revealed {
+                s.revealed += 1;
+            }
+
+            match (&cell.truth, &cell.guess) {
                 (Some(t), Some(g)) if t != g => s.errors += 1,
                 _ => {}
             }
@@ -231,245 +781,996 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
         s
     }
 
+    /// True if the cell has a letter that doesn't match the answer.
+    fn is_wrong(&self, x: u16, y: u16) -> bool {
+        match (&self.get(x, y).truth, &self.get(x, y).guess) {
+            (Some(t), Some(g)) => t != g,
+            _ => false,
+        }
+    }
+
     fn is_game_over(&self) -> bool {
         let status = self.get_status();
 
         status.errors == 0 && status.cells == status.guesses
     }
 
-    fn draw_cell(&mut self, x: u16, y: u16) {
-        write!(self.stdout, "{}", cursor::Goto(x * 4 + 1, y * 3 + 1)).unwrap();
+    /// Space taken up by the coordinate ruler above and left of the grid.
+    fn ruler_offset(&self) -> (u16, u16) {
+        if self.show_ruler {
+            (3, 1)
+        } else {
+            (0, 0)
+        }
+    }
 
-        let cross = match (x == self.width - 1, y == self.height - 1) {
-            (true, true) => "\u{251b}",
-            (true, false) => "\u{252b}",
-            (false, true) => "\u{253b}",
-            (false, false) => "\u{254b}",
-        };
+    /// The escape code for a foreground color, or nothing in mono mode.
+    fn fg(&self, color: theme::Color) -> String {
+        if self.mono {
+            String::new()
+        } else {
+            color.fg()
+        }
+    }
 
-        match self.get(x, y).truth {
-            Some(_t) => {
-                // Use an  arrow on the right border if this is the selected cell
-                // and we're in Mode::EditAcross
+    /// The escape code for a background color, or nothing in mono mode.
+    fn bg(&self, color: theme::Color) -> String {
+        if self.mono {
+            String::new()
+        } else {
+            color.bg()
+        }
+    }
 
-                let right_border = match self.mode {
-                    Mode::EditAcross if self.cursor_x == x && self.cursor_y == y => {
-                        format!("{}\u{25B6}{}", color::Fg(color::LightRed), style::Reset)
-                    }
-                    _ => "\u{2503}".to_string(),
-                };
+    /// The size of the terminal, or the size it was last time if that can't
+    /// be found out, e.g. while drawing into memory rather than a terminal.
+    fn terminal_size(&self) -> (u16, u16) {
+        termion::terminal_size().unwrap_or(self.term_size)
+    }
 
-                match self.get(x, y).clue_number {
-                    Some(n) => write!(self.stdout, "{:<3}\u{2503}", n).unwrap(),
-                    None => write!(self.stdout, "   \u{2503}").unwrap(),
-                };
-                write!(self.stdout, "{}", cursor::Goto(x * 4 + 1, y * 3 + 2)).unwrap();
+    /// Calculate the terminal coordinates of the top left corner of a cell.
+    fn cell_origin(&self, x: u16, y: u16) -> (u16, u16) {
+        let (offset_x, offset_y) = self.ruler_offset();
+        let (cell_width, cell_height) = self.cell_size();
 
-                match self.get(x, y).guess {
-                    Some(g) => write!(
-                        self.stdout,
-                        " {}{}{}{} {}",
-                        style::Bold,
-                        color::Fg(color::Cyan),
-                        g,
-                        style::Reset,
-                        right_border
-                    )
-                    .unwrap(),
-                    None => write!(self.stdout, "   {}", right_border).unwrap(),
-                };
-                write!(self.stdout, "{}", cursor::Goto(x * 4 + 1, y * 3 + 3)).unwrap();
+        (
+            self.margin + offset_x + x.saturating_sub(self.grid_scroll_x) * cell_width + 1,
+            offset_y + y.saturating_sub(self.grid_scroll_y) * cell_height + 1,
+        )
+    }
 
-                // Draw a downward-pointing arrow in the bottom border if this is the
-                // selected cell and we're in Mode::EditDown
+    /// Calculate the terminal column where the clue list starts.
+    fn clues_left(&self) -> u16 {
+        self.cell_origin(self.grid_scroll_x + self.grid_cols, 0).0 + 2
+    }
 
-                match self.mode {
-                    Mode::EditDown if self.cursor_x == x && self.cursor_y == y => write!(
-                        self.stdout,
-                        "\u{2501}{}\u{25BC}{}\u{2501}\u{254B}",
-                        color::Fg(color::LightRed),
-                        style::Reset
-                    )
-                    .unwrap(),
-                    _ => write!(self.stdout, "\u{2501}\u{2501}\u{2501}{}", cross).unwrap(),
-                }
+    /// Whether the cell is scrolled into view.
+    fn on_screen(&self, x: u16, y: u16) -> bool {
+        x >= self.grid_scroll_x
+            && x < self.grid_scroll_x + self.grid_cols
+            && y >= self.grid_scroll_y
+            && y < self.grid_scroll_y + self.grid_rows
+    }
+
+    /// Scroll a grid that's too big to show all at once so the cursor stays
+    /// on screen. Returns whether the grid moved.
+    fn scroll_to_cursor(&mut self) -> bool {
+        let old = (self.grid_scroll_x, self.grid_scroll_y);
+
+        self.grid_scroll_x = scroll_to(
+            self.grid_scroll_x,
+            self.cursor_x,
+            self.grid_cols,
+            self.width,
+        );
+        self.grid_scroll_y = scroll_to(
+            self.grid_scroll_y,
+            self.cursor_y,
+            self.grid_rows,
+            self.height,
+        );
+
+        (self.grid_scroll_x, self.grid_scroll_y) != old
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16) {
+        if !self.on_screen(x, y) {
+            return;
+        }
+
+        if self.overview {
+            self.draw_overview_cell(x, y);
+            return;
+        }
+
+        let (sx, sy) = self.cell_origin(x, y);
+
+        write!(self.stdout, "{}", cursor::Goto(sx, sy)).unwrap();
+
+        let cross = render::border_corner(x == self.width - 1, y == self.height - 1);
+
+        // While the block pattern of a diagramless puzzle is hidden, black cells
+        // look just like empty ones, and the player marks their own.
+
+        let inner = (self.cell_width - 1) as usize;
+        let guess_row = self.guess_row();
+
+        let grid = self.fg(self.theme.grid);
+        let right = format!("{}\u{2503}{}", grid, style::Reset);
+        let bottom = format!(
+            "{}{}{}{}",
+            grid,
+            "\u{2501}".repeat(inner),
+            cross,
+            style::Reset
+        );
+
+        if self.hide_blocks && self.get(x, y).user_black {
+            self.fill_cell(sx, sy, "\u{2592}", &right, &bottom);
+        } else if self.get(x, y).truth.is_some() || self.hide_blocks {
+            let bg = self.cell_bg(x, y);
+
+            // Compact cells have no room for the clue number.
+
+            if guess_row > 0 {
+                let corner = self.corner_text(x, y);
+                write!(
+                    self.stdout,
+                    "{}{}{}{}{}",
+                    bg,
+                    self.fg(self.theme.clue_number),
+                    corner,
+                    style::Reset,
+                    right
+                )
+                .unwrap();
             }
-            None => {
-                // Draw a black cell
 
-                write!(self.stdout, "\u{2588}\u{2588}\u{2588}\u{2503}").unwrap();
-                write!(self.stdout, "{}", cursor::Goto(x * 4 + 1, y * 3 + 2)).unwrap();
-                write!(self.stdout, "\u{2588}\u{2588}\u{2588}\u{2503}").unwrap();
-                write!(self.stdout, "{}", cursor::Goto(x * 4 + 1, y * 3 + 3)).unwrap();
-                write!(self.stdout, "\u{2501}\u{2501}\u{2501}{}", cross).unwrap();
+            write!(self.stdout, "{}", cursor::Goto(sx, sy + guess_row)).unwrap();
+
+            let guess_color = match self.color_override {
+                Some(ref c) => c.clone(),
+                None if self.get(x, y).marked_wrong => {
+                    format!("{}{}", style::CrossedOut, self.fg(self.theme.error))
+                }
+                None if self.check_as_you_type && self.is_wrong(x, y) => self.fg(self.theme.error),
+                None => self.fg(self.theme.guess),
+            };
+
+            // Pencilled letters are faint, confident ones bold.
+
+            let weight = if self.get(x, y).pencil {
+                format!("{}{}", style::Faint, style::Italic)
+            } else {
+                style::Bold.to_string()
+            };
+
+            match self.get(x, y).guess {
+                Some(ref g) => write!(
+                    self.stdout,
+                    "{}{}{}{}{}{}",
+                    bg,
+                    weight,
+                    guess_color,
+                    render::guess_text(g, inner),
+                    style::Reset,
+                    right
+                )
+                .unwrap(),
+                None => write!(
+                    self.stdout,
+                    "{}{}{}{}",
+                    bg,
+                    " ".repeat(inner),
+                    style::Reset,
+                    right
+                )
+                .unwrap(),
+            };
+            write!(self.stdout, "{}", cursor::Goto(sx, sy + guess_row + 1)).unwrap();
+            write!(self.stdout, "{}", bottom).unwrap();
+
+            if self.cursor_x == x && self.cursor_y == y {
+                self.draw_cursor_indicator(x, y);
             }
+        } else {
+            // Draw a black cell, a little lighter if the cursor is on it.
+
+            let shade = if self.is_cursor(x, y) {
+                "\u{2593}"
+            } else {
+                "\u{2588}"
+            };
+
+            self.fill_cell(sx, sy, shade, &right, &bottom);
         }
     }
 
-    fn draw_cursor_cell(&mut self) {
-        let x = self.cursor_x;
-        let y = self.cursor_y;
+    /// True if this is the cursor's cell and the grid is being played.
+    fn is_cursor(&self, x: u16, y: u16) -> bool {
+        (x, y) == (self.cursor_x, self.cursor_y) && self.is_playing()
+    }
 
-        self.draw_cell(x, y);
+    /// Cells of the word being edited get a shaded background, as do other
+    /// cells with the same letter as the cursor cell when those are shown.
+    fn cell_bg(&self, x: u16, y: u16) -> String {
+        // The cursor's cell is in reverse video so it's easy to find, except
+        // in a word that's already in reverse video in mono mode, where it's
+        // left plain instead.
+
+        let cursor = self.is_cursor(x, y);
+        let word = self.highlighted.contains(&(x, y));
+        let same_letter = self.same_letter.contains(&(x, y));
+
+        match (cursor, word, same_letter, self.mono) {
+            (true, true, _, true) => self.bg(self.theme.background),
+            (true, _, _, _) | (_, true, _, true) => style::Invert.to_string(),
+            (_, true, _, false) => self.bg(self.theme.word),
+            (_, _, true, true) => style::Underline.to_string(),
+            (_, _, true, false) => self.bg(self.theme.same_letter),
+            _ => self.bg(self.theme.background),
+        }
     }
 
-    fn draw_status_bar(&mut self) {
-        let (term_width, term_height) = termion::terminal_size().unwrap();
+    /// Draw a cell of the overview as a single character: its letter, `#` if
+    /// it's black, or a dot if it's empty. The cursor cell is in reverse
+    /// video.
+    fn draw_overview_cell(&mut self, x: u16, y: u16) {
+        let (sx, sy) = self.cell_origin(x, y);
+        let cell = self.get(x, y);
 
-        let s = self.get_status();
+        let black = if self.hide_blocks {
+            cell.user_black
+        } else {
+            cell.truth.is_none()
+        };
 
-        write!(
-            self.stdout,
-            "{}{}{}{}{}",
-            cursor::Goto(0, term_height),
-            color::Bg(color::White),
-            color::Fg(color::Black),
-            " ".repeat(term_width as usize),
-            cursor::Goto(0, term_height),
-        )
-        .unwrap();
+        let (color, c) = match cell.guess {
+            _ if black => (String::new(), '#'),
+            Some(ref g) if cell.marked_wrong => {
+                (self.fg(self.theme.error), g.chars().next().unwrap_or(' '))
+            }
+            Some(ref g) => (self.fg(self.theme.guess), g.chars().next().unwrap_or(' ')),
+            None => (String::new(), '\u{b7}'),
+        };
+
+        let bg = match (black, self.is_cursor(x, y)) {
+            (true, true) => style::Invert.to_string(),
+            (true, false) => String::new(),
+            _ => self.cell_bg(x, y),
+        };
 
         write!(
             self.stdout,
-            "puzterm {} G{}/{} E{} T{}:{:02}:{:02}",
-            self.version,
-            s.guesses,
-            s.cells,
-            if self.hint_num_errors {
-                s.errors.to_string()
-            } else {
-                "?".to_string()
-            },
-            self.stopwatch.elapsed().as_secs() / 60 / 60,
-            (self.stopwatch.elapsed().as_secs() / 60) % 60,
-            self.stopwatch.elapsed().as_secs() % 60,
+            "{}{}{}{}{}{}",
+            cursor::Goto(sx, sy),
+            bg,
+            style::Bold,
+            color,
+            c,
+            style::Reset
         )
         .unwrap();
-
-        write!(self.stdout, "{}", style::Reset).unwrap();
     }
 
-    fn draw_all(&mut self) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                self.draw_cell(x, y);
-            }
+    /// Terminal columns and rows taken by each cell. In the overview they're
+    /// single characters without borders.
+    fn cell_size(&self) -> (u16, u16) {
+        if self.overview {
+            (1, 1)
+        } else {
+            (self.cell_width, self.cell_height)
         }
+    }
 
-        self.draw_title();
-        self.draw_clues();
-        self.draw_status_bar();
-        self.draw_cursor();
+    /// Fill the inside of the cell at the given terminal coordinates with a
+    /// shading character, and draw its right and bottom borders.
+    fn fill_cell(&mut self, sx: u16, sy: u16, shade: &str, right: &str, bottom: &str) {
+        let inner = (self.cell_width - 1) as usize;
 
-        self.stdout.flush().unwrap();
-    }
+        for row in 0..self.cell_height - 1 {
+            write!(
+                self.stdout,
+                "{}{}{}",
+                cursor::Goto(sx, sy + row),
+                shade.repeat(inner),
+                right
+            )
+            .unwrap();
+        }
 
-    fn draw_title(&mut self) {
         write!(
             self.stdout,
-            "{}{:.width$}{}{:.width$}",
-            cursor::Goto(0, self.height * 3 + 1),
-            self.title,
-            cursor::Goto(0, self.height * 3 + 2),
-            self.author,
-            width = self.width as usize * 4
+            "{}{}",
+            cursor::Goto(sx, sy + self.cell_height - 1),
+            bottom
         )
         .unwrap();
     }
 
-    fn draw_clues(&mut self) {
-        let (term_width, term_height) = termion::terminal_size().unwrap();
-
-        let clues_width = term_width - self.width * 4 - 2;
-        let clues_height = term_height - 1;
+    /// The row within a cell that the letter goes in, below the clue number
+    /// unless cells are compact.
+    fn guess_row(&self) -> u16 {
+        self.cell_size().1.saturating_sub(2)
+    }
 
-        // Across / Down labels aren't truncated, so they'll wrap into
-        // the game board if we don't have enough space to display them.
+    /// Whether the word being worked on runs across, going by the current or
+    /// most recent edit mode.
+    fn is_across(&self) -> bool {
+        match (self.mode, self.last_edit_mode) {
+            (Mode::EditAcross, _) => true,
+            (Mode::EditDown, _) => false,
+            (_, Mode::EditDown) => false,
+            _ => true,
+        }
+    }
 
-        if clues_width < 6 {
-            return;
+    /// The top row of a cell: the clue number, and a circle in the corner if
+    /// the cell is circled. Neither is shown while blocks are hidden, and the
+    /// number isn't shown while numbers are toggled off.
+    fn corner_text(&self, x: u16, y: u16) -> String {
+        if self.hide_blocks {
+            return "   ".to_string();
         }
 
-        let x = self.cursor_x;
-        let y = self.cursor_y;
-        let cursor_clue_number = self.get(x, y).clue_number;
+        let cell = self.get(x, y);
+        let number = cell.clue_number.filter(|_| self.show_numbers);
 
-        let mut strings = Vec::new();
+        render::corner_text(number, cell.circled)
+    }
 
-        strings.push(format!("{}Across{}", style::Bold, style::Reset));
-        strings.push("".into());
+    /// Mark the cell being edited, in the style picked in the config.
+    fn draw_cursor_indicator(&mut self, x: u16, y: u16) {
+        let (sx, sy) = self.cell_origin(x, y);
 
-        for cell in &self.grid {
-            if let Some(ref clue) = cell.clue_across {
-                let mut tmp = format!("{}. {}", cell.clue_number.unwrap(), clue);
-                tmp.truncate(clues_width as usize);
+        let across = match self.mode {
+            Mode::EditAcross => true,
+            Mode::EditDown => false,
+            _ => return,
+        };
 
-                match cursor_clue_number {
-                    Some(n) if n == cell.clue_number.unwrap() => {
-                        strings.push(format!("{}{}{}", style::Bold, tmp, style::Reset));
-                    }
-                    _ => strings.push(tmp),
-                }
-            }
-        }
+        let inner = self.cell_width - 1;
+        let guess_row = self.guess_row();
 
-        strings.push("".into());
-        strings.push(format!("{}Down{}", style::Bold, style::Reset));
-        strings.push("".into());
+        let guess = match self.get(x, y).guess {
+            Some(ref g) => render::guess_text(g, inner as usize),
+            None => " ".repeat(inner as usize),
+        };
 
-        for cell in &self.grid {
-            if let Some(ref clue) = cell.clue_down {
-                let mut tmp = format!("{}. {}", cell.clue_number.unwrap(), clue);
-                tmp.truncate(clues_width as usize);
+        match self.config.cursor_style {
+            CursorStyle::Arrow | CursorStyle::Blink => {
+                let blink = match self.config.cursor_style {
+                    CursorStyle::Blink => style::Blink.to_string(),
+                    _ => String::new(),
+                };
 
-                match cursor_clue_number {
-                    Some(n) if n == cell.clue_number.unwrap() => {
-                        strings.push(format!("{}{}{}", style::Bold, tmp, style::Reset));
-                    }
-                    _ => strings.push(tmp),
+                // An arrow on the right border when editing across, or in the
+                // bottom border when editing down.
+
+                if across {
+                    write!(
+                        self.stdout,
+                        "{}{}{}\u{25B6}{}",
+                        cursor::Goto(sx + inner, sy + guess_row),
+                        self.fg(self.theme.cursor),
+                        blink,
+                        style::Reset
+                    )
+                    .unwrap();
+                } else {
+                    write!(
+                        self.stdout,
+                        "{}{}{}\u{25BC}{}",
+                        cursor::Goto(sx + inner / 2, sy + guess_row + 1),
+                        self.fg(self.theme.cursor),
+                        blink,
+                        style::Reset
+                    )
+                    .unwrap();
                 }
             }
-        }
+            CursorStyle::Highlight => {
+                if guess_row > 0 {
+                    let corner = self.corner_text(x, y);
 
-        for i in 0..clues_height {
-            write!(
-                self.stdout,
-                "{}{}",
-                cursor::Goto(self.width * 4 + 3, i as u16 + 1),
-                clear::UntilNewline
-            )
-            .unwrap();
+                    write!(
+                        self.stdout,
+                        "{}{}{}{}",
+                        cursor::Goto(sx, sy),
+                        style::Invert,
+                        corner,
+                        style::Reset
+                    )
+                    .unwrap();
+                }
+
+                write!(
+                    self.stdout,
+                    "{}{}{}{}{}",
+                    cursor::Goto(sx, sy + guess_row),
+                    style::Bold,
+                    style::Invert,
+                    guess,
+                    style::Reset
+                )
+                .unwrap();
+            }
+            CursorStyle::Underline => {
+                write!(
+                    self.stdout,
+                    "{}{}{}{}{}{}{}",
+                    cursor::Goto(sx, sy + guess_row),
+                    self.cell_bg(x, y),
+                    style::Underline,
+                    style::Bold,
+                    self.fg(self.theme.guess),
+                    guess,
+                    style::Reset
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    fn draw_cursor_cell(&mut self) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        self.update_highlight();
+        self.draw_cell(x, y);
+    }
+
+    /// Work out which cells belong to the word being edited, and redraw the
+    /// ones that have gone in or out of it.
+    fn update_highlight(&mut self) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        let word = match self.mode {
+            Mode::EditAcross | Mode::EditDown if self.is_open(x, y) => {
+                self.word_cells(x, y, self.is_across())
+            }
+            _ => Vec::new(),
+        };
+
+        if word == self.highlighted {
+            return;
+        }
+
+        let old = std::mem::replace(&mut self.highlighted, word);
+
+        for (x, y) in old.into_iter().chain(self.highlighted.clone()) {
+            self.draw_cell(x, y);
+        }
+    }
+
+    /// Work out which other cells hold the same letter as the cursor cell, and
+    /// redraw the ones that have started or stopped matching.
+    fn update_same_letter(&mut self) {
+        let mut cells = Vec::new();
+
+        if self.show_same_letter {
+            if let Some(ref letter) = self.get(self.cursor_x, self.cursor_y).guess {
+                for y in 0..self.height {
+                    for x in 0..self.width {
+                        if (x, y) != (self.cursor_x, self.cursor_y)
+                            && self.get(x, y).guess.as_ref() == Some(letter)
+                        {
+                            cells.push((x, y));
+                        }
+                    }
+                }
+            }
+        }
+
+        if cells == self.same_letter {
+            return;
+        }
+
+        let old = std::mem::replace(&mut self.same_letter, cells);
+
+        for (x, y) in old.into_iter().chain(self.same_letter.clone()) {
+            self.draw_cell(x, y);
+        }
+    }
+
+    fn status_lines(&self) -> Vec<String> {
+        let s = self.get_status();
+
+        let errors = if self.hint_num_errors {
+            s.errors.to_string()
+        } else {
+            "?".to_string()
+        };
+        let mut time = format_time(self.elapsed().as_secs());
+
+        if let Mode::Pause = self.mode {
+            time.insert(0, '\u{23f8}');
+        }
+
+        let mut lines = if self.verbose_status {
+            let across = self.is_across();
+            let word = if self.is_open(self.cursor_x, self.cursor_y) {
+                let cells = self.word_cells(self.cursor_x, self.cursor_y, across);
+                let filled = cells
+                    .iter()
+                    .filter(|&&(x, y)| self.get(x, y).guess.is_some())
+                    .count();
+
+                format!(
+                    "{} {}/{}",
+                    if across { "Across" } else { "Down" },
+                    filled,
+                    cells.len()
+                )
+            } else {
+                "-".to_string()
+            };
+
+            vec![
+                format!("puzterm {} | Time {}", self.version, time),
+                format!(
+                    "Filled {}/{} | Errors {} | Penalty +{}:{:02} ({} checks, {} reveals)",
+                    s.guesses,
+                    s.cells,
+                    errors,
+                    self.penalty.as_secs() / 60,
+                    self.penalty.as_secs() % 60,
+                    self.checks,
+                    self.reveals
+                ),
+                format!(
+                    "Cell {}{} | Word {} | Revealed {}",
+                    column_label(self.cursor_x),
+                    self.cursor_y + 1,
+                    word,
+                    s.revealed
+                ),
+            ]
+        } else {
+            let mut line = format!(
+                "puzterm {} G{}/{} E{} T{}",
+                self.version, s.guesses, s.cells, errors, time
+            );
+
+            if s.revealed > 0 {
+                line.push_str(&format!(" R{}", s.revealed));
+            }
+
+            vec![line]
+        };
+
+        if self.pencil_mode {
+            lines[0].push_str(" | Pencil");
+        }
+
+        if !self.notes_text().trim().is_empty() {
+            lines[0].push_str(" | Notes");
+        }
+
+        if let Some(ref message) = self.message {
+            lines[0].push_str(&format!(" | {}", message));
+        }
+
+        match self.prompt {
+            Some((Prompt::Rebus, ref input)) => {
+                lines[0].push_str(&format!(" | Rebus: {}_", input));
+            }
+            Some((Prompt::GotoClue, ref input)) => {
+                lines[0].push_str(&format!(" | Go to clue (e.g. 12a): {}_", input));
+            }
+            None => {}
+        }
+
+        lines
+    }
+
+    /// Rows at the bottom of the terminal taken up by the status bar.
+    fn status_height(&self) -> u16 {
+        if self.verbose_status {
+            4
+        } else {
+            2
+        }
+    }
+
+    /// Colors for the status bar, which need putting back after any other
+    /// styling in it.
+    fn status_style(&self) -> String {
+        if self.mono {
+            style::Invert.to_string()
+        } else {
+            format!(
+                "{}{}",
+                self.bg(self.theme.status_bg),
+                self.fg(self.theme.status_fg)
+            )
+        }
+    }
+
+    /// The number and text of the across or down clue for the word the
+    /// cursor is in, if there is one.
+    fn cursor_clue(&self, across: bool) -> Option<(u16, &str)> {
+        if !self.is_open(self.cursor_x, self.cursor_y) {
+            return None;
+        }
+
+        let (x, y) = self.word_cells(self.cursor_x, self.cursor_y, across)[0];
+        let cell = self.get(x, y);
+        let clue = if across {
+            &cell.clue_across
+        } else {
+            &cell.clue_down
+        };
+
+        match (cell.clue_number, clue) {
+            (Some(n), Some(clue)) => Some((n, clue.as_str())),
+            _ => None,
         }
+    }
+
+    /// The across and down clues at the cursor, side by side, with the one
+    /// being worked on in bold.
+    fn clue_status_line(&self, width: u16) -> String {
+        // While editing, say which way letters are going in, so it's clear
+        // at a glance after switching with space.
 
-        for (i, string) in strings
+        let direction = match self.mode {
+            Mode::EditAcross => "ACROSS \u{25B6} ",
+            Mode::EditDown => "DOWN \u{25BC} ",
+            _ => "",
+        };
+
+        let half = (width as usize).saturating_sub(3 + text::width(direction)) / 2;
+        let active = self.is_across();
+
+        let clues = [true, false]
             .iter()
-            .skip(self.clues_scroll as usize)
-            .take(clues_height as usize)
-            .enumerate()
-        {
+            .map(|&across| {
+                let text = match self.cursor_clue(across) {
+                    Some((n, clue)) => format!("{}{} {}", n, if across { "A" } else { "D" }, clue),
+                    None => String::new(),
+                };
+                let text = text::truncate(&text, half);
+
+                if across == active {
+                    format!(
+                        "{}{}{}{}",
+                        style::Bold,
+                        text,
+                        style::Reset,
+                        self.status_style()
+                    )
+                } else {
+                    text
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        format!("{}{}", direction, clues)
+    }
+
+    fn draw_status_bar(&mut self) {
+        let (term_width, term_height) = self.terminal_size();
+
+        let mut lines = self.status_lines();
+        lines.insert(0, self.clue_status_line(term_width));
+
+        let top = term_height + 1 - lines.len() as u16;
+
+        write!(self.stdout, "{}", self.status_style()).unwrap();
+
+        // Terminal coordinates start at 1. Column 0 happened to work in most
+        // terminals, but not all of them.
+
+        for (i, line) in lines.iter().enumerate() {
+            write!(
+                self.stdout,
+                "{}{}{}{}",
+                cursor::Goto(1, top + i as u16),
+                " ".repeat(term_width as usize),
+                cursor::Goto(1, top + i as u16),
+                line
+            )
+            .unwrap();
+        }
+
+        write!(self.stdout, "{}", style::Reset).unwrap();
+    }
+
+    fn draw_all(&mut self) {
+        self.term_size = self.terminal_size();
+
+        // When centering, the clue list is kept to a comfortable width and
+        // whatever room is left over is split between the two sides.
+
+        self.margin = 0;
+
+        // A grid too big for the terminal shows as many columns and rows as
+        // fit, and scrolls to follow the cursor. When it's too tall, room is
+        // kept below it for the title and the status bar.
+
+        let (offset_x, offset_y) = self.ruler_offset();
+        let (cell_width, cell_height) = self.cell_size();
+        let (term_width, term_height) = self.term_size;
+
+        let room = term_width.saturating_sub(offset_x + 1) / cell_width;
+        self.grid_cols = self.width.min(room);
+
+        let room = term_height.saturating_sub(offset_y + 1) / cell_height;
+        self.grid_rows = if room >= self.height {
+            self.height
+        } else {
+            term_height.saturating_sub(offset_y + self.status_height() + 2) / cell_height
+        };
+
+        self.scroll_to_cursor();
+
+        if self.center {
+            let used = self.clues_left() - 1 + CENTERED_CLUES_WIDTH;
+            self.margin = self.term_size.0.saturating_sub(used) / 2;
+        }
+
+        // Without room for even one cell, drawing the grid just makes a mess,
+        // so ask for more room instead.
+
+        self.too_small = self.grid_cols == 0 || self.grid_rows == 0;
+
+        if self.too_small {
+            let (need_width, need_height) =
+                self.cell_origin(self.grid_scroll_x + 1, self.grid_scroll_y + 1);
+            let need_height = need_height + self.status_height() + 1;
+
+            self.draw_message_screen(&[
+                "Terminal too small".into(),
+                "".into(),
+                format!("Please make it at least {}x{}.", need_width, need_height),
+            ]);
+            self.stdout.flush().unwrap();
+            return;
+        }
+
+        self.draw_grid();
+        self.draw_title();
+        self.draw_clues();
+        self.draw_status_bar();
+        self.draw_cursor();
+
+        self.stdout.flush().unwrap();
+    }
+
+    /// Draw every cell on screen, and the ruler if it's shown.
+    fn draw_grid(&mut self) {
+        for y in self.grid_scroll_y..self.grid_scroll_y + self.grid_rows {
+            for x in self.grid_scroll_x..self.grid_scroll_x + self.grid_cols {
+                self.draw_cell(x, y);
+            }
+        }
+
+        if self.show_ruler {
+            self.draw_ruler();
+        }
+    }
+
+    fn draw_title(&mut self) {
+        let (sx, sy) = self.cell_origin(0, self.grid_scroll_y + self.grid_rows);
+
+        write!(
+            self.stdout,
+            "{}{:.width$}{}{:.width$}",
+            cursor::Goto(sx, sy),
+            self.title,
+            cursor::Goto(sx, sy + 1),
+            self.author,
+            width = (self.grid_cols * self.cell_size().0) as usize
+        )
+        .unwrap();
+    }
+
+    /// Lines for the across or down half of the clue list, wrapped to the
+    /// given width, with the clue at the cursor in bold. Each line comes with
+    /// the word its clue is for.
+    fn clue_lines(&self, across: bool, width: u16) -> Vec<ClueLine> {
+        let cursor_clue_number = self.get(self.cursor_x, self.cursor_y).clue_number;
+
+        let mut strings = Vec::new();
+
+        // The heading is cut to fit like the clues are, so a narrow clue list
+        // doesn't spill back over the grid.
+
+        let heading = if across { "Across" } else { "Down" };
+        strings.push((
+            None,
+            format!(
+                "{}{}{}",
+                style::Bold,
+                text::truncate(heading, width as usize),
+                style::Reset
+            ),
+        ));
+        strings.push((None, "".into()));
+
+        for (i, cell) in self.grid.iter().enumerate() {
+            let clue = if across {
+                &cell.clue_across
+            } else {
+                &cell.clue_down
+            };
+
+            if let Some(ref clue) = *clue {
+                let (x, y) = self.cell_at(i);
+                let word = Some(Word { x, y, across });
+                let number = format!("{}. ", cell.clue_number.unwrap());
+                let indent = " ".repeat(number.len());
+
+                // Continuation lines line up under the start of the text.
+
+                let lines = wrap(clue, (width as usize).saturating_sub(number.len()))
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, line)| {
+                        let start = if i == 0 { &number } else { &indent };
+                        let line = format!("{}{}", start, line);
+
+                        text::truncate(&line, width as usize)
+                    });
+
+                match cursor_clue_number {
+                    Some(n) if n == cell.clue_number.unwrap() => strings.extend(
+                        lines.map(|l| (word, format!("{}{}{}", style::Bold, l, style::Reset))),
+                    ),
+                    _ => strings.extend(lines.map(|l| (word, l))),
+                }
+            }
+        }
+
+        strings
+    }
+
+    /// How many columns the clue list has to work with.
+    fn clues_width(&self) -> u16 {
+        let (term_width, _) = self.terminal_size();
+        let width = term_width.saturating_sub(self.clues_left() - 1);
+
+        if self.center {
+            width.min(CENTERED_CLUES_WIDTH)
+        } else {
+            width
+        }
+    }
+
+    /// With plenty of room, Across and Down go side by side rather than one
+    /// after the other.
+    fn clues_side_by_side(&self) -> bool {
+        self.clues_width() >= 62
+    }
+
+    /// The columns of the clue list: where each starts, its lines, and how
+    /// far it's scrolled.
+    fn clue_columns(&self) -> Vec<(u16, Vec<ClueLine>, u16)> {
+        let clues_left = self.clues_left();
+        let clues_width = self.clues_width();
+
+        if self.clues_side_by_side() {
+            let column_width = (clues_width - 2) / 2;
+
+            vec![
+                (
+                    clues_left,
+                    self.clue_lines(true, column_width),
+                    self.clues_scroll,
+                ),
+                (
+                    clues_left + column_width + 2,
+                    self.clue_lines(false, column_width),
+                    self.down_clues_scroll,
+                ),
+            ]
+        } else {
+            let mut strings = self.clue_lines(true, clues_width);
+            strings.push((None, "".into()));
+            strings.extend(self.clue_lines(false, clues_width));
+
+            vec![(clues_left, strings, self.clues_scroll)]
+        }
+    }
+
+    fn draw_clues(&mut self) {
+        let (_, term_height) = self.terminal_size();
+
+        let clues_left = self.clues_left();
+        let clues_width = self.clues_width();
+        let clues_height = term_height.saturating_sub(self.status_height());
+
+        if clues_width == 0 {
+            return;
+        }
+
+        let columns = self.clue_columns();
+
+        for i in 0..clues_height {
             write!(
                 self.stdout,
-                "{}",
-                cursor::Goto(self.width * 4 + 3, i as u16 + 1)
+                "{}{}",
+                cursor::Goto(clues_left, i + 1),
+                clear::UntilNewline
             )
             .unwrap();
-            write!(self.stdout, "{}", string).unwrap();
+        }
+
+        for (left, strings, scroll) in columns {
+            for (i, (_, string)) in strings
+                .iter()
+                .skip(scroll as usize)
+                .take(clues_height as usize)
+                .enumerate()
+            {
+                write!(self.stdout, "{}", cursor::Goto(left, i as u16 + 1)).unwrap();
+                write!(self.stdout, "{}", string).unwrap();
+            }
         }
     }
 
     fn draw_cursor(&mut self) {
+        let cursor = (self.cursor_x, self.cursor_y);
+
+        // The cursor's cell is drawn in reverse video, so the cell it left
+        // needs drawing again, unless the whole grid has scrolled to keep up
+        // with it.
+
+        if self.is_playing() && !self.too_small {
+            if self.scroll_to_cursor() {
+                self.draw_grid();
+            } else if let Some((x, y)) = self.drawn_cursor.filter(|&c| c != cursor) {
+                self.draw_cell(x, y);
+                self.draw_cell(cursor.0, cursor.1);
+            }
+
+            self.drawn_cursor = Some(cursor);
+        }
+
+        let (sx, sy) = self.cell_origin(self.cursor_x, self.cursor_y);
+
         write!(
             self.stdout,
             "{}",
-            cursor::Goto(self.cursor_x * 4 + 2, self.cursor_y * 3 + 2)
+            cursor::Goto(sx + (self.cell_size().0 - 1) / 2, sy + self.guess_row())
         )
         .unwrap();
     }
 
+    /// Draw column letters above the grid and row numbers to the left of it.
+    fn draw_ruler(&mut self) {
+        for x in self.grid_scroll_x..self.grid_scroll_x + self.grid_cols {
+            let (sx, _) = self.cell_origin(x, 0);
+
+            write!(
+                self.stdout,
+                "{}{}",
+                cursor::Goto(sx + (self.cell_size().0 - 1) / 2, 1),
+                column_label(x)
+            )
+            .unwrap();
+        }
+
+        for y in self.grid_scroll_y..self.grid_scroll_y + self.grid_rows {
+            let (_, sy) = self.cell_origin(0, y);
+
+            write!(
+                self.stdout,
+                "{}{:>2}",
+                cursor::Goto(self.margin + 1, sy + self.guess_row()),
+                y + 1
+            )
+            .unwrap();
+        }
+    }
+
     fn draw_message_screen(&mut self, messages: &[String]) {
         write!(self.stdout, "{}", clear::All).unwrap();
 
-        let (term_width, term_height) = termion::terminal_size().unwrap();
+        let (term_width, term_height) = self.terminal_size();
 
         let height = messages.len() as u16;
 
@@ -482,8 +1783,8 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
                 self.stdout,
                 "{}{}",
                 cursor::Goto(
-                    (term_width - message.len() as u16) / 2,
-                    term_height / 2 - height / 2 + i as u16,
+                    (term_width.saturating_sub(text::width(message) as u16) / 2).max(1),
+                    (term_height / 2).saturating_sub(height / 2).max(1) + i as u16,
                 ),
                 message
             )
@@ -551,9 +1852,10 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
             return x;
         }
 
-        match self.get(x - 1, y).truth {
-            Some(_) => x - 1,
-            _ => x,
+        if self.is_open(x - 1, y) {
+            x - 1
+        } else {
+            x
         }
     }
 
@@ -565,9 +1867,10 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
             return x;
         }
 
-        match self.get(x + 1, y).truth {
-            Some(_) => x + 1,
-            _ => x,
+        if self.is_open(x + 1, y) {
+            x + 1
+        } else {
+            x
         }
     }
 
@@ -579,9 +1882,10 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
             return y;
         }
 
-        match self.get(x, y - 1).truth {
-            Some(_) => y - 1,
-            _ => y,
+        if self.is_open(x, y - 1) {
+            y - 1
+        } else {
+            y
         }
     }
 
@@ -593,29 +1897,82 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
             return y;
         }
 
-        match self.get(x, y + 1).truth {
-            Some(_) => y + 1,
-            _ => y,
+        if self.is_open(x, y + 1) {
+            y + 1
+        } else {
+            y
         }
     }
 
-    fn select_move(&mut self, direction: Direction) {
-        match direction {
-            Direction::Up => {
-                self.cursor_y = self.up(self.cursor_y);
-            }
-            Direction::Down => {
-                self.cursor_y = self.down(self.cursor_y);
+    /// The cells of the across or down word running through given x, y
+    /// coordinates, from first to last.
+    fn word_cells(&self, x: u16, y: u16, across: bool) -> Vec<(u16, u16)> {
+        let step_back = |x, y| {
+            if across {
+                (self.edit_left(x, y), y)
+            } else {
+                (x, self.edit_up(x, y))
             }
-            Direction::Left => {
-                self.cursor_x = self.left(self.cursor_x);
+        };
+        let step_forward = |x, y| {
+            if across {
+                (self.edit_right(x, y), y)
+            } else {
+                (x, self.edit_down(x, y))
             }
-            Direction::Right => {
-                self.cursor_x = self.right(self.cursor_x);
+        };
+
+        let mut start = (x, y);
+
+        loop {
+            let prev = step_back(start.0, start.1);
+            if prev == start {
+                break;
             }
+            start = prev;
         }
 
-        self.draw_clues();
+        let mut cells = vec![start];
+
+        loop {
+            let last = cells[cells.len() - 1];
+            let next = step_forward(last.0, last.1);
+            if next == last {
+                break;
+            }
+            cells.push(next);
+        }
+
+        cells
+    }
+
+    fn select_move(&mut self, direction: Direction) {
+        // Without wrapping, the cursor just stops at the edges of the grid.
+
+        match direction {
+            Direction::Up => {
+                if self.wrap_navigation || self.cursor_y > 0 {
+                    self.cursor_y = self.up(self.cursor_y);
+                }
+            }
+            Direction::Down => {
+                if self.wrap_navigation || self.cursor_y + 1 < self.height {
+                    self.cursor_y = self.down(self.cursor_y);
+                }
+            }
+            Direction::Left => {
+                if self.wrap_navigation || self.cursor_x > 0 {
+                    self.cursor_x = self.left(self.cursor_x);
+                }
+            }
+            Direction::Right => {
+                if self.wrap_navigation || self.cursor_x + 1 < self.width {
+                    self.cursor_x = self.right(self.cursor_x);
+                }
+            }
+        }
+
+        self.draw_clues();
     }
 
     fn edit_move(&mut self, direction: Direction) {
@@ -641,10 +1998,67 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
         self.draw_cursor_cell();
     }
 
+    /// Move with an arrow key in edit mode. With `continue_words`, going past
+    /// the end of a word moves on to the next word that way.
+    fn edit_arrow(&mut self, direction: Direction) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        self.edit_move(direction);
+
+        if !self.config.continue_words || (self.cursor_x, self.cursor_y) != (x, y) {
+            return;
+        }
+
+        match (direction, self.is_across()) {
+            (Direction::Right, true) | (Direction::Down, false) => self.next_clue(),
+            (Direction::Left, true) | (Direction::Up, false) => {
+                self.prev_clue();
+
+                let across = self.is_across();
+                let cells = self.word_cells(self.cursor_x, self.cursor_y, across);
+
+                if let Some(&(x, y)) = cells.last() {
+                    self.edit_at(x, y, across);
+                }
+            }
+            _ => self.edit_past_blocks(direction),
+        }
+    }
+
+    /// Move to the nearest open cell in a direction, across any black cells in
+    /// the way. Stays put if there's nothing before the edge of the grid.
+    fn edit_past_blocks(&mut self, direction: Direction) {
+        let (dx, dy) = match direction {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        };
+
+        let mut x = self.cursor_x as i32;
+        let mut y = self.cursor_y as i32;
+
+        loop {
+            x += dx;
+            y += dy;
+
+            if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+                return;
+            }
+
+            if self.is_open(x as u16, y as u16) {
+                let across = self.is_across();
+                self.edit_at(x as u16, y as u16, across);
+                return;
+            }
+        }
+    }
+
     /// Enter an appropriate edit mode for the current cursor position.
     fn edit_mode(&mut self) {
         // Can't edit a black cell
-        if self.get(self.cursor_x, self.cursor_y).truth.is_none() {
+        if !self.is_open(self.cursor_x, self.cursor_y) {
             return;
         }
 
@@ -672,6 +2086,7 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
         self.last_edit_mode = self.mode;
 
         self.draw_cursor_cell();
+        self.draw_status_bar();
     }
 
     /// Enter select mode
@@ -681,229 +2096,2701 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> Game<R, W> {
         self.draw_cursor_cell();
     }
 
+    /// End the game, recording the puzzle as completed if every square is
+    /// right, however much help it took.
+    fn finish(&mut self) {
+        if self.is_game_over() {
+            self.record_completion();
+        }
+
+        self.game_over_mode();
+    }
+
+    fn record_completion(&self) {
+        let path = match self.records_path {
+            Some(ref path) => path,
+            None => return,
+        };
+
+        let mut records = Records::load(path);
+        records.record(self.record_key, self.elapsed().as_secs());
+
+        // Not worth interrupting the end of the game over.
+        let _ = records.save(path);
+    }
+
     /// Enter game over mode
     fn game_over_mode(&mut self) {
         self.mode = Mode::GameOver;
 
-        self.draw_message_screen(&[
-            "Game Over.".into(),
-            "".into(),
-            "Press any key to quit.".into(),
-        ]);
+        self.stopwatch.stop();
+
+        let mut messages = self.summary(&self.get_status(), self.elapsed());
+
+        messages.push("".into());
+        messages.push(if self.from_menu {
+            "Press any key to return to the menu.".into()
+        } else {
+            "Press any key to quit.".into()
+        });
+
+        self.draw_message_screen(&messages);
 
         self.draw_status_bar();
         self.stdout.flush().unwrap();
+    }
 
-        self.stopwatch.stop();
+    /// True unless the player had help: anything checked or revealed,
+    /// including the error count and a diagramless block pattern.
+    fn solved_cleanly(&self) -> bool {
+        self.checks == 0 && self.reveals == 0 && !self.structure_revealed
     }
 
-    /// Put a guess into the current cell
-    fn input(&mut self, c: char) {
-        let x = self.cursor_x;
-        let y = self.cursor_y;
+    /// The grid as it stands and the clues, as plain text. Black cells are
+    /// dots and empty ones dashes, as in the .puz format. In a diagramless
+    /// puzzle, only the cells the player marked black are dots.
+    fn export_text(&self) -> String {
+        let mut s = format!("{}\n{}\n\n", self.title, self.author);
+
+        for row in self.grid.chunks(self.width as usize) {
+            let line: String = row
+                .iter()
+                .map(|cell| match (&cell.truth, &cell.guess) {
+                    _ if self.hide_blocks && cell.user_black => '.',
+                    (None, _) if !self.hide_blocks => '.',
+                    (_, Some(g)) => g.chars().next().unwrap_or('-'),
+                    _ => '-',
+                })
+                .collect();
+
+            s.push_str(&line);
+            s.push('\n');
+        }
 
-        // This doesn't work for certain characters, and I'm okay with that.
+        for &across in &[true, false] {
+            s.push_str(if across { "\nAcross\n\n" } else { "\nDown\n\n" });
 
-        let upper = c.to_uppercase().collect::<Vec<_>>().swap_remove(0);
+            for cell in &self.grid {
+                let clue = if across {
+                    &cell.clue_across
+                } else {
+                    &cell.clue_down
+                };
 
-        self.get_mut(x, y).guess = Some(upper);
+                if let (Some(n), Some(clue)) = (cell.clue_number, clue) {
+                    s.push_str(&format!("{}. {}\n", n, clue));
+                }
+            }
+        }
 
-        self.edit_next();
+        s
     }
 
-    /// Removes the guess at the current cell
-    fn unguess(&mut self) {
-        let x = self.cursor_x;
-        let y = self.cursor_y;
+    /// What the end screen says about how the puzzle went.
+    fn summary(&self, status: &GameStatus, time: Duration) -> Vec<String> {
+        let revealed = status.revealed;
+        let solved = status.errors == 0 && status.guesses == status.cells && revealed == 0;
+
+        // Only a puzzle solved without any revealed answers counts as solved,
+        // and only one solved without checking anything gets a plain "Solved!".
+
+        let heading = match (solved, self.solved_cleanly()) {
+            (true, true) => "Solved!",
+            (true, false) => "Solved with help",
+            (false, _) => "Game Over.",
+        };
+
+        let mut messages = vec![heading.to_string(), "".into()];
+
+        messages.push(format!("Time: {}", format_time(time.as_secs())));
+
+        if status.guesses < status.cells || status.errors > 0 {
+            messages.push(format!(
+                "{} of {} squares filled, {} wrong.",
+                status.guesses, status.cells, status.errors
+            ));
+        } else {
+            messages.push(format!("All {} squares filled.", status.cells));
+        }
+
+        if self.solved_cleanly() {
+            messages.push("No checks, no reveals. Well done!".into());
+        } else {
+            messages.push(format!(
+                "{} {}, {} {}.",
+                self.checks,
+                if self.checks == 1 { "check" } else { "checks" },
+                self.reveals,
+                if self.reveals == 1 {
+                    "reveal"
+                } else {
+                    "reveals"
+                }
+            ));
+        }
+
+        if revealed > 0 {
+            messages.push(format!(
+                "{} {} revealed.",
+                revealed,
+                if revealed == 1 {
+                    "square was"
+                } else {
+                    "squares were"
+                }
+            ));
+        }
+
+        if self.structure_revealed {
+            messages.push("The block pattern was revealed.".into());
+        }
+
+        if self.penalty > Duration::from_secs(0) {
+            messages.push(format!(
+                "Including a penalty of +{}",
+                format_time(self.penalty.as_secs())
+            ));
+        }
+
+        if self.paused.as_secs() > 0 {
+            messages.push(format!(
+                "Paused for {} on top of that.",
+                format_time(self.paused.as_secs())
+            ));
+        }
+
+        messages
+    }
+
+    /// Change the guess in a cell, and whether it's pencilled in. A wrong mark
+    /// goes away with the guess it was for.
+    fn set_guess(&mut self, x: u16, y: u16, guess: Option<String>, pencil: bool) {
+        let cell = self.get_mut(x, y);
+
+        cell.guess = guess;
+        cell.marked_wrong = false;
+        cell.pencil = pencil;
+    }
+
+    /// Change a guess in a way that can be undone.
+    fn edit_guess(&mut self, x: u16, y: u16, guess: Option<String>, pencil: bool) {
+        let old = self.get(x, y).guess.clone();
+        let old_pencil = self.get(x, y).pencil;
+
+        if (&old, old_pencil) != (&guess, pencil) {
+            self.history.push(Edit {
+                x,
+                y,
+                old,
+                new: guess.clone(),
+                old_pencil,
+                new_pencil: pencil,
+            });
+        }
+
+        self.set_guess(x, y, guess, pencil);
+    }
+
+    fn undo(&mut self) {
+        match self.history.undo() {
+            Some(edit) => self.restore_guess(edit.x, edit.y, edit.old, edit.old_pencil),
+            None => self.show_message("Nothing to undo"),
+        }
+    }
 
-        self.get_mut(x, y).guess = None;
+    fn redo(&mut self) {
+        match self.history.redo() {
+            Some(edit) => self.restore_guess(edit.x, edit.y, edit.new, edit.new_pencil),
+            None => self.show_message("Nothing to redo"),
+        }
+    }
+
+    /// Put a guess back as part of an undo or redo, and move to its cell.
+    fn restore_guess(&mut self, x: u16, y: u16, guess: Option<String>, pencil: bool) {
+        let old_x = self.cursor_x;
+        let old_y = self.cursor_y;
+
+        self.set_guess(x, y, guess, pencil);
+
+        self.cursor_x = x;
+        self.cursor_y = y;
+
+        self.draw_cell(old_x, old_y);
         self.draw_cursor_cell();
+        self.draw_clues();
         self.draw_status_bar();
     }
 
-    /// Move the cursor to the next cell to be edited
-    fn edit_next(&mut self) {
+    /// Put a guess into the current cell, then move on to the next one.
+    fn input(&mut self, c: char) {
         let x = self.cursor_x;
         let y = self.cursor_y;
 
-        match self.mode {
-            Mode::EditAcross => self.edit_move(Direction::Right),
-            Mode::EditDown => self.edit_move(Direction::Down),
-            _ => {}
-        }
+        // This doesn't work for certain characters, and I'm okay with that.
 
-        self.draw_cell(x, y);
-        self.draw_cursor_cell();
-        self.draw_status_bar();
+        let upper = c.to_uppercase().collect::<Vec<_>>().swap_remove(0);
+
+        let across = self.is_across();
+        let was_filled = self.is_filled_at(x, y, across);
+
+        let pencil = self.pencil_mode;
+        self.edit_guess(x, y, Some(upper.to_string()), pencil);
+
+        self.edit_next(!was_filled && self.is_filled_at(x, y, across));
     }
 
-    /// Move the cursor to the previous cell to be edited
-    fn edit_prev(&mut self) {
+    /// Briefly flash the cursor cell green if its guess is right or red if it's
+    /// wrong. Empty cells are left alone.
+    fn check_letter(&mut self) {
         let x = self.cursor_x;
         let y = self.cursor_y;
 
-        match self.mode {
-            Mode::EditAcross => self.edit_move(Direction::Left),
-            Mode::EditDown => self.edit_move(Direction::Up),
-            _ => {}
-        }
+        let right = match (&self.get(x, y).truth, &self.get(x, y).guess) {
+            (Some(t), Some(g)) => t == g,
+            _ => return,
+        };
+
+        self.add_check_penalty();
 
+        self.color_override = Some(if self.mono {
+            if right {
+                style::Bold.to_string()
+            } else {
+                style::Invert.to_string()
+            }
+        } else if right {
+            format!("{}{}", self.bg(self.theme.correct), color::Fg(color::Black))
+        } else {
+            format!(
+                "{}{}",
+                self.bg(self.theme.incorrect),
+                color::Fg(color::White)
+            )
+        });
+        self.draw_cell(x, y);
+        self.stdout.flush().unwrap();
+
+        std::thread::sleep(Duration::from_millis(300));
+
+        self.color_override = None;
         self.draw_cell(x, y);
-        self.draw_cursor_cell();
         self.draw_status_bar();
+        self.stdout.flush().unwrap();
     }
 
-    fn clues_scroll_up(&mut self) {
-        if self.clues_scroll <= 5 {
-            self.clues_scroll = 0;
-        } else {
-            self.clues_scroll -= 5;
+    /// Mark the filled in cells of the current word that are wrong.
+    fn check_word(&mut self) {
+        if !self.is_open(self.cursor_x, self.cursor_y) {
+            return;
         }
 
-        self.draw_clues();
+        let cells = self.word_cells(self.cursor_x, self.cursor_y, self.is_across());
+
+        self.check_cells(&cells);
     }
 
-    fn clues_scroll_down(&mut self) {
-        self.clues_scroll += 5;
+    /// Mark every filled in cell in the grid that's wrong.
+    fn check_puzzle(&mut self) {
+        let mut cells = Vec::new();
 
-        self.draw_clues();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                cells.push((x, y));
+            }
+        }
+
+        self.check_cells(&cells);
     }
 
-    fn pause(&mut self) {
-        self.mode = Mode::Pause;
+    fn check_cells(&mut self, cells: &[(u16, u16)]) {
+        self.add_check_penalty();
 
-        self.draw_message_screen(&[
-            "Game Paused".into(),
-            "".into(),
-            "Press p to continue.".into(),
-            "Press ctrl-c to quit.".into(),
-        ]);
+        let mut wrong = 0;
 
-        self.draw_status_bar();
-        self.stdout.flush().unwrap();
+        for &(x, y) in cells {
+            let cell = self.get_mut(x, y);
 
-        self.stopwatch.stop();
-    }
+            if let (Some(t), Some(g)) = (&cell.truth, &cell.guess) {
+                if t != g {
+                    cell.marked_wrong = true;
+                    wrong += 1;
+                }
+            }
 
-    fn unpause(&mut self) {
-        self.mode = Mode::Select;
+            self.draw_cell(x, y);
+        }
 
-        write!(self.stdout, "{}", clear::All).unwrap();
+        self.show_message(&match wrong {
+            0 => "No mistakes".to_string(),
+            1 => "1 mistake".to_string(),
+            n => format!("{} mistakes", n),
+        });
+    }
 
-        self.draw_all();
+    /// Fill in the answer for the cell under the cursor.
+    fn reveal_letter(&mut self) {
+        let cells = [(self.cursor_x, self.cursor_y)];
 
-        self.stopwatch.start();
+        self.reveal_cells(&cells);
     }
 
-    fn toggle_hint_num_errors(&mut self) {
-        self.hint_num_errors = !self.hint_num_errors;
+    /// Fill in the answer for the first cell of the current word, to get
+    /// started on it.
+    fn reveal_first_letter(&mut self) {
+        if !self.is_open(self.cursor_x, self.cursor_y) {
+            return;
+        }
 
-        self.draw_status_bar();
-        self.stdout.flush().unwrap();
+        let cells = self.word_cells(self.cursor_x, self.cursor_y, self.is_across());
+
+        self.reveal_cells(&cells[..1]);
     }
 
-    fn start(&mut self) {
-        self.stopwatch.start();
+    /// Fill in the answers for the current word.
+    fn reveal_word(&mut self) {
+        if !self.is_open(self.cursor_x, self.cursor_y) {
+            return;
+        }
 
-        loop {
-            self.tick += 1;
+        let cells = self.word_cells(self.cursor_x, self.cursor_y, self.is_across());
 
-            if !self.update() {
-                break;
-            }
+        self.reveal_cells(&cells);
+    }
 
-            match self.mode {
-                Mode::GameOver => {}
-                _ => {
-                    if self.is_game_over() {
-                        self.game_over_mode();
-                    }
-                }
-            }
+    /// Fill in the whole grid.
+    fn reveal_puzzle(&mut self) {
+        let mut cells = Vec::new();
 
-            if self.tick % 10 == 0 {
-                self.draw_status_bar();
-                self.draw_cursor();
-                self.stdout.flush().unwrap();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                cells.push((x, y));
             }
-
-            std::thread::sleep(Duration::from_millis(10));
         }
+
+        self.reveal_cells(&cells);
     }
 
-    fn update(&mut self) -> bool {
-        while let Some(b) = self.stdin.next() {
-            if let Ok(c) = b {
-                use termion::event::Key::*;
-
-                match self.mode {
-                    Mode::Pause => match c {
-                        Char('p') | Char('\n') | Esc => self.unpause(),
-                        Ctrl('c') => return false,
-                        _ => {}
-                    },
-                    Mode::Select => match c {
-                        PageUp | Char('[') => self.clues_scroll_up(),
-                        PageDown | Char(']') => self.clues_scroll_down(),
-                        Char('h') | Char('a') | Left => self.select_move(Direction::Left),
-                        Char('j') | Char('s') | Down => self.select_move(Direction::Down),
-                        Char('k') | Char('w') | Up => self.select_move(Direction::Up),
-                        Char('l') | Char('d') | Right => self.select_move(Direction::Right),
-                        Char('q') | Char('p') | Ctrl('c') | Esc => self.pause(),
-                        Char('e') => self.toggle_hint_num_errors(),
-                        Char('\n') | Char('i') => self.edit_mode(),
-                        _ => {}
-                    },
-                    Mode::EditAcross | Mode::EditDown => match c {
-                        Delete => self.unguess(),
-                        PageUp => self.clues_scroll_up(),
-                        PageDown => self.clues_scroll_down(),
-                        Backspace => self.edit_prev(),
-                        Left => self.edit_move(Direction::Left),
-                        Down => self.edit_move(Direction::Down),
-                        Up => self.edit_move(Direction::Up),
-                        Right => self.edit_move(Direction::Right),
-                        Char('\n') | Esc => self.select_mode(),
-                        Char(' ') => self.edit_direction(),
-                        Char(c) if c.is_alphanumeric() => {
-                            self.input(c);
-                        }
-                        _ => {}
-                    },
-                    Mode::GameOver => return false,
-                }
+    /// Put the answer into each of the cells that doesn't already have it.
+    fn reveal_cells(&mut self, cells: &[(u16, u16)]) {
+        let mut any = false;
 
-                self.draw_cursor();
-                self.stdout.flush().unwrap();
+        for &(x, y) in cells {
+            let cell = self.get_mut(x, y);
+
+            if cell.truth.is_none() || cell.guess == cell.truth {
+                continue;
             }
+
+            cell.guess = cell.truth.clone();
+            cell.revealed = true;
+            cell.pencil = false;
+            cell.marked_wrong = false;
+            any = true;
+
+            self.history.forget(x, y);
+            self.draw_cell(x, y);
         }
 
-        true
+        if any {
+            self.add_reveal_penalty();
+        }
+
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
     }
-}
 
-fn main() {
-    let filename = env::args().nth(1).unwrap_or_else(|| {
-        eprintln!("Usage: puzterm <file>");
-        ::std::process::exit(1);
-    });
+    /// Open a prompt in the status bar for typing several letters into the
+    /// current cell.
+    fn rebus_prompt(&mut self) {
+        if !self.is_open(self.cursor_x, self.cursor_y) {
+            return;
+        }
 
-    let mut f = File::open(&Path::new(&filename)).unwrap();
-    let mut v = Vec::new();
-    f.read_to_end(&mut v).ok();
+        self.open_prompt(Prompt::Rebus);
+    }
 
-    let p = match puzfile::parse_all(&v[..]) {
-        Ok((_, p)) => p,
-        Err(Err::Incomplete(x)) => panic!("incomplete: {:?}", x),
-        Err(Err::Error(e)) => panic!("error: {:?}", e),
-        Err(Err::Failure(e)) => panic!("failure: {:?}", e),
-    };
+    fn open_prompt(&mut self, prompt: Prompt) {
+        self.prompt = Some((prompt, String::new()));
 
-    let stdout = io::stdout();
-    let stdout = stdout.lock();
-    let stdout = stdout.into_raw_mode().unwrap();
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
 
-    let stdin = async_stdin();
+    /// Handle a key while a prompt is open.
+    fn prompt_key(&mut self, c: Key) {
+        use termion::event::Key::*;
 
-    init(stdin, stdout, &p);
+        let (prompt, mut input) = match self.prompt.take() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+
+        match (prompt, c) {
+            (_, Esc) => {}
+            (_, Char('\n')) => self.submit_prompt(prompt, input),
+            (_, Backspace) => {
+                input.pop();
+                self.prompt = Some((prompt, input));
+            }
+            (Prompt::Rebus, Char(c)) if c.is_alphanumeric() => {
+                input.extend(c.to_uppercase());
+                self.prompt = Some((prompt, input));
+            }
+            (Prompt::GotoClue, Char(c)) if c.is_ascii_digit() => {
+                input.push(c);
+                self.prompt = Some((prompt, input));
+            }
+            (Prompt::GotoClue, Char(c)) if "aAdD".contains(c) => {
+                input.extend(c.to_uppercase());
+                self.submit_prompt(prompt, input);
+            }
+            _ => self.prompt = Some((prompt, input)),
+        }
+
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    fn submit_prompt(&mut self, prompt: Prompt, input: String) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        match prompt {
+            Prompt::Rebus if input.is_empty() => {
+                self.edit_guess(x, y, None, false);
+                self.draw_cursor_cell();
+            }
+            Prompt::Rebus => {
+                let across = self.is_across();
+                let was_filled = self.is_filled_at(x, y, across);
+
+                let pencil = self.pencil_mode;
+                self.edit_guess(x, y, Some(input), pencil);
+                self.edit_next(!was_filled && self.is_filled_at(x, y, across));
+            }
+            Prompt::GotoClue => self.goto_clue(&input),
+        }
+    }
+
+    /// Start editing the clue with the given number, e.g. `12`, `12A` or `12D`.
+    /// Without a direction, across is picked if there's a choice. Clues that
+    /// don't exist are ignored.
+    fn goto_clue(&mut self, input: &str) {
+        let digits = input.trim_end_matches(&['A', 'D'][..]);
+
+        let (x, y) = match digits.parse().ok().and_then(|n| self.clue_cells.get(&n)) {
+            Some(&cell) => cell,
+            None => return,
+        };
+
+        let has_across = self.get(x, y).clue_across.is_some();
+        let has_down = self.get(x, y).clue_down.is_some();
+
+        let across = match &input[digits.len()..] {
+            "A" if has_across => true,
+            "D" if has_down => false,
+            "" => has_across,
+            _ => return,
+        };
+
+        self.edit_at(x, y, across);
+    }
+
+    /// Removes the guess at the current cell
+    fn unguess(&mut self) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        self.edit_guess(x, y, None, false);
+        self.draw_cursor_cell();
+        self.draw_status_bar();
+    }
+
+    /// Move the cursor to the next cell to be edited. `completed` is true if
+    /// the guess just entered filled the last empty cell of the word.
+    fn edit_next(&mut self, completed: bool) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        if completed && self.config.post_word_flow == PostWordFlow::Crossing && self.edit_crossing()
+        {
+            return;
+        }
+
+        if completed && self.auto_advance && self.edit_next_unfilled() {
+            return;
+        }
+
+        match self.mode {
+            Mode::EditAcross => self.edit_move(Direction::Right),
+            Mode::EditDown => self.edit_move(Direction::Down),
+            _ => {}
+        }
+
+        if self.skip_filled {
+            self.skip_filled_cells();
+        }
+
+        self.draw_cell(x, y);
+        self.draw_cursor_cell();
+        self.draw_status_bar();
+    }
+
+    /// Having just finished the word being edited, move on to the first empty
+    /// cell of the next clue in Tab order that still has one. Returns true if
+    /// the cursor moved.
+    fn edit_next_unfilled(&mut self) -> bool {
+        let words = self.clue_order();
+        let i = self.clue_index(&words);
+
+        let empty = (1..words.len())
+            .map(|offset| words[(i + offset) % words.len()])
+            .filter_map(|w| {
+                self.word_cells(w.x, w.y, w.across)
+                    .into_iter()
+                    .find(|&(x, y)| self.get(x, y).guess.is_none())
+                    .map(|(x, y)| (x, y, w.across))
+            })
+            .next();
+
+        match empty {
+            Some((x, y, across)) => {
+                self.edit_at(x, y, across);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move the cursor on to the next empty cell of the word being edited,
+    /// or to the end of the word if the rest of it is full.
+    fn skip_filled_cells(&mut self) {
+        let across = match self.mode {
+            Mode::EditAcross => true,
+            Mode::EditDown => false,
+            _ => return,
+        };
+
+        let word = self.word_cells(self.cursor_x, self.cursor_y, across);
+        let start = match word
+            .iter()
+            .position(|&c| c == (self.cursor_x, self.cursor_y))
+        {
+            Some(start) => start,
+            None => return,
+        };
+
+        let target = word[start..]
+            .iter()
+            .find(|&&(x, y)| self.get(x, y).guess.is_none())
+            .or_else(|| word.last());
+
+        if let Some(&(x, y)) = target {
+            self.cursor_x = x;
+            self.cursor_y = y;
+        }
+    }
+
+    /// Having just finished the word being edited, carry on with the word
+    /// crossing it at the cursor instead. Returns true if the cursor moved on
+    /// to it.
+    fn edit_crossing(&mut self) -> bool {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        let across = match self.mode {
+            Mode::EditAcross => true,
+            Mode::EditDown => false,
+            _ => return false,
+        };
+
+        let crossing = self.word_cells(x, y, !across);
+
+        if crossing.len() < 2 {
+            return false;
+        }
+
+        // Continue from the first empty cell of the crossing word, if any.
+
+        let (nx, ny) = crossing
+            .iter()
+            .cloned()
+            .find(|&(cx, cy)| self.get(cx, cy).guess.is_none())
+            .unwrap_or((x, y));
+
+        self.mode = if across {
+            Mode::EditDown
+        } else {
+            Mode::EditAcross
+        };
+        self.last_edit_mode = self.mode;
+
+        self.cursor_x = nx;
+        self.cursor_y = ny;
+
+        self.draw_cell(x, y);
+        self.draw_cursor_cell();
+        self.draw_clues();
+        self.draw_status_bar();
+
+        true
+    }
+
+    /// Move the cursor to the given cell and start editing in the given
+    /// direction.
+    fn edit_at(&mut self, x: u16, y: u16, across: bool) {
+        let old_x = self.cursor_x;
+        let old_y = self.cursor_y;
+
+        self.cursor_x = x;
+        self.cursor_y = y;
+
+        self.mode = if across {
+            Mode::EditAcross
+        } else {
+            Mode::EditDown
+        };
+        self.last_edit_mode = self.mode;
+
+        self.draw_cell(old_x, old_y);
+        self.draw_cursor_cell();
+        self.draw_clues();
+        self.draw_status_bar();
+    }
+
+    /// True if every cell of the word has a guess in it.
+    fn is_word_filled(&self, word: &Word) -> bool {
+        self.is_filled_at(word.x, word.y, word.across)
+    }
+
+    /// True if every cell of the word running through a cell has a guess in
+    /// it.
+    fn is_filled_at(&self, x: u16, y: u16, across: bool) -> bool {
+        self.word_cells(x, y, across)
+            .iter()
+            .all(|&(x, y)| self.get(x, y).guess.is_some())
+    }
+
+    /// Clues in the order Tab goes through them: all the across clues, then all
+    /// the down ones.
+    fn clue_order(&self) -> Vec<Word> {
+        let mut words: Vec<Word> = self.words.iter().filter(|w| w.across).cloned().collect();
+        words.extend(self.words.iter().filter(|w| !w.across));
+
+        words
+    }
+
+    /// Move to the first cell of the clue `offset` places after the current
+    /// one, wrapping around from the last down clue to the first across one.
+    fn step_clue(&mut self, offset: isize) {
+        let words = self.clue_order();
+
+        if words.is_empty() {
+            return;
+        }
+
+        let i = self.clue_index(&words) as isize;
+        let next = words[(i + offset).rem_euclid(words.len() as isize) as usize];
+
+        self.edit_at(next.x, next.y, next.across);
+    }
+
+    /// Where the word being edited is in `words`, or 0 if it isn't there.
+    fn clue_index(&self, words: &[Word]) -> usize {
+        let across = self.is_across();
+        let start = self.word_cells(self.cursor_x, self.cursor_y, across)[0];
+
+        words
+            .iter()
+            .position(|w| w.across == across && (w.x, w.y) == start)
+            .unwrap_or(0)
+    }
+
+    fn next_clue(&mut self) {
+        self.step_clue(1);
+    }
+
+    fn prev_clue(&mut self) {
+        self.step_clue(-1);
+    }
+
+    /// Start editing the first across clue.
+    fn start_solving(&mut self) {
+        if let Some(&word) = self.words.iter().find(|w| w.across) {
+            self.edit_at(word.x, word.y, true);
+        }
+    }
+
+    /// Start editing the first word, in reading order, that still has empty
+    /// cells.
+    fn first_unsolved(&mut self) {
+        let word = match self.words.iter().find(|w| !self.is_word_filled(w)) {
+            Some(&w) => w,
+            None => {
+                self.show_message("No unsolved clues");
+                return;
+            }
+        };
+
+        let (x, y) = self
+            .word_cells(word.x, word.y, word.across)
+            .into_iter()
+            .find(|&(x, y)| self.get(x, y).guess.is_none())
+            .unwrap_or((word.x, word.y));
+
+        self.edit_at(x, y, word.across);
+    }
+
+    /// The next open cell without a guess after the cursor, in reading order,
+    /// wrapping around from the bottom right to the top left.
+    fn next_empty_cell(&self) -> Option<(u16, u16)> {
+        self.next_cell_where(|cell| cell.guess.is_none())
+    }
+
+    /// The next open cell with a pencilled letter after the cursor, wrapping
+    /// around the same way.
+    fn next_pencil_cell(&self) -> Option<(u16, u16)> {
+        self.next_cell_where(|cell| cell.pencil && cell.guess.is_some())
+    }
+
+    fn next_cell_where<F: Fn(&Cell) -> bool>(&self, f: F) -> Option<(u16, u16)> {
+        let len = self.grid.len();
+        let start = self.cursor_y as usize * self.width as usize + self.cursor_x as usize;
+
+        (1..=len)
+            .map(|i| (start + i) % len)
+            .map(|i| self.cell_at(i))
+            .find(|&(x, y)| self.is_open(x, y) && f(self.get(x, y)))
+    }
+
+    /// Jump to the next empty cell, keeping the current mode and direction.
+    fn goto_next_empty(&mut self) {
+        match self.next_empty_cell() {
+            Some((x, y)) => self.jump_to(x, y),
+            None => self.show_message("No empty squares"),
+        }
+    }
+
+    /// Jump to the next pencilled letter, to take another look at it.
+    fn goto_next_pencil(&mut self) {
+        match self.next_pencil_cell() {
+            Some((x, y)) => self.jump_to(x, y),
+            None => self.show_message("No pencilled squares"),
+        }
+    }
+
+    /// Move the cursor to a cell, keeping the current mode and direction.
+    fn jump_to(&mut self, x: u16, y: u16) {
+        match self.mode {
+            Mode::Select => {
+                self.cursor_x = x;
+                self.cursor_y = y;
+                self.draw_clues();
+            }
+            _ => {
+                let across = self.is_across();
+                self.edit_at(x, y, across);
+            }
+        }
+    }
+
+    /// Move the cursor to the previous cell to be edited
+    fn edit_prev(&mut self) {
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        match self.mode {
+            Mode::EditAcross => self.edit_move(Direction::Left),
+            Mode::EditDown => self.edit_move(Direction::Up),
+            _ => {}
+        }
+
+        self.draw_cell(x, y);
+        self.draw_cursor_cell();
+        self.draw_status_bar();
+    }
+
+    /// Clear the current cell, or if it's already empty, step back and clear
+    /// the previous one, so repeated presses erase a run of letters.
+    fn backspace(&mut self) {
+        if self.get(self.cursor_x, self.cursor_y).guess.is_none() {
+            self.edit_prev();
+        }
+
+        self.unguess();
+    }
+
+    fn handle_mouse(&mut self, m: MouseEvent) {
+        match self.mode {
+            Mode::Select | Mode::EditAcross | Mode::EditDown
+                if self.prompt.is_none() && self.overlay.is_none() => {}
+            _ => return,
+        }
+
+        match m {
+            MouseEvent::Press(MouseButton::Left, x, y) => self.click(x, y),
+            MouseEvent::Press(MouseButton::WheelUp, x, _) if x >= self.clues_left() => {
+                let across = self.clue_column_at(x);
+                self.clues_scroll_up(across);
+            }
+            MouseEvent::Press(MouseButton::WheelDown, x, _) if x >= self.clues_left() => {
+                let across = self.clue_column_at(x);
+                self.clues_scroll_down(across);
+            }
+            _ => {}
+        }
+    }
+
+    /// Edit the cell or clue at the given terminal coordinates. Clicking the
+    /// cell being edited switches direction.
+    fn click(&mut self, sx: u16, sy: u16) {
+        if sx >= self.clues_left() {
+            self.click_clue(sx, sy);
+            return;
+        }
+
+        let (offset_x, offset_y) = self.ruler_offset();
+
+        if sx <= self.margin + offset_x || sy <= offset_y {
+            return;
+        }
+
+        let (cell_width, cell_height) = self.cell_size();
+        let x = self.grid_scroll_x + (sx - self.margin - offset_x - 1) / cell_width;
+        let y = self.grid_scroll_y + (sy - offset_y - 1) / cell_height;
+
+        if !self.on_screen(x, y) || !self.is_open(x, y) {
+            return;
+        }
+
+        match self.mode {
+            Mode::EditAcross | Mode::EditDown if (x, y) == (self.cursor_x, self.cursor_y) => {
+                self.edit_direction();
+                return;
+            }
+            _ => {}
+        }
+
+        let old_x = self.cursor_x;
+        let old_y = self.cursor_y;
+
+        self.cursor_x = x;
+        self.cursor_y = y;
+
+        self.draw_cell(old_x, old_y);
+        self.edit_mode();
+        self.draw_clues();
+        self.draw_status_bar();
+    }
+
+    /// Whether the clue list at the given terminal column is showing across
+    /// clues, as opposed to down ones side by side with them.
+    fn clue_column_at(&self, sx: u16) -> bool {
+        self.clue_columns().iter().filter(|c| c.0 <= sx).count() <= 1
+    }
+
+    fn click_clue(&mut self, sx: u16, sy: u16) {
+        let (_, term_height) = self.terminal_size();
+
+        if sy > term_height.saturating_sub(self.status_height()) {
+            return;
+        }
+
+        let word = self
+            .clue_columns()
+            .into_iter()
+            .rev()
+            .find(|c| c.0 <= sx)
+            .and_then(|(_, strings, scroll)| {
+                (scroll as usize + sy as usize)
+                    .checked_sub(1)
+                    .and_then(|i| strings.get(i))
+                    .and_then(|line| line.0)
+            });
+
+        if let Some(word) = word {
+            self.edit_at(word.x, word.y, word.across);
+        }
+    }
+
+    /// The scroll position of the across or down clues.
+    fn clues_scroll_mut(&mut self, across: bool) -> &mut u16 {
+        if self.clues_side_by_side() && !across {
+            &mut self.down_clues_scroll
+        } else {
+            &mut self.clues_scroll
+        }
+    }
+
+    fn clues_scroll_up(&mut self, across: bool) {
+        let scroll = self.clues_scroll_mut(across);
+        *scroll = scroll.saturating_sub(5);
+
+        self.draw_clues();
+    }
+
+    /// Scroll the clues down, but no further than it takes to show the last
+    /// one at the bottom.
+    fn clues_scroll_down(&mut self, across: bool) {
+        let (_, term_height) = self.terminal_size();
+        let clues_height = term_height.saturating_sub(self.status_height());
+
+        let column = if self.clues_side_by_side() && !across {
+            1
+        } else {
+            0
+        };
+        let lines = self.clue_columns().swap_remove(column).1.len();
+        let max = lines.saturating_sub(clues_height as usize) as u16;
+
+        let scroll = self.clues_scroll_mut(across);
+        *scroll = scroll.saturating_add(5).min(max);
+
+        self.draw_clues();
+    }
+
+    fn pause(&mut self) {
+        self.mode = Mode::Pause;
+
+        if self.paused_since.is_none() {
+            self.paused_since = Some(Instant::now());
+        }
+
+        let mut messages = vec![
+            "Game Paused".into(),
+            "".into(),
+            "Press p to continue.".into(),
+        ];
+
+        if self.from_menu {
+            messages.push("Press m for the puzzle menu.".into());
+        }
+
+        messages.push("Press r to start over.".into());
+        messages.push("Press ctrl-c to quit.".into());
+
+        self.draw_message_screen(&messages);
+
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+
+        self.stopwatch.stop();
+    }
+
+    /// Draw everything again from scratch if the terminal has changed size
+    /// since the last time.
+    fn check_resize(&mut self) {
+        let size = match termion::terminal_size() {
+            Ok(size) => size,
+            Err(_) => return,
+        };
+
+        if size == self.term_size {
+            return;
+        }
+
+        self.term_size = size;
+
+        match self.mode {
+            Mode::Intro => self.intro(),
+            Mode::Pause if self.confirm_reset => self.confirm_reset(),
+            Mode::Pause => self.pause(),
+            _ if self.overlay == Some(Overlay::Help) => self.show_help(),
+            _ if self.overlay.is_some() => self.show_notes(),
+            Mode::GameOver => self.game_over_mode(),
+            _ => {
+                write!(self.stdout, "{}", clear::All).unwrap();
+                self.draw_all();
+            }
+        }
+    }
+
+    /// Cover the grid with a list of what every key does.
+    fn show_help(&mut self) {
+        self.overlay = Some(Overlay::Help);
+
+        let mut messages = vec!["Keys".to_string(), "".into()];
+        messages.extend(self.config.keys.help());
+        messages.push("".into());
+        messages.push("Press any key to go back.".into());
+
+        self.draw_message_screen(&messages);
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    /// Cover the grid with the puzzle's notes, as far down as they've been
+    /// scrolled.
+    fn show_notes(&mut self) {
+        let (term_width, term_height) = self.terminal_size();
+
+        let lines = self.notes_lines(term_width);
+        let page = self.notes_page(term_height);
+        let max_scroll = lines.len().saturating_sub(page);
+
+        let scroll = match self.overlay {
+            Some(Overlay::Notes(scroll)) => scroll.min(max_scroll),
+            _ => 0,
+        };
+        self.overlay = Some(Overlay::Notes(scroll));
+
+        let mut messages = vec!["Notes".to_string(), "".into()];
+        messages.extend(lines.into_iter().skip(scroll).take(page));
+        messages.push("".into());
+        messages.push(if max_scroll > 0 {
+            "Scroll with j and k, or press any other key to go back.".into()
+        } else {
+            "Press any key to go back.".into()
+        });
+
+        self.draw_message_screen(&messages);
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    /// The puzzle's notes, followed by any warnings about the file.
+    fn notes_text(&self) -> String {
+        let mut text = self.notes.clone();
+
+        for warning in &self.warnings {
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+
+            text.push_str(warning);
+        }
+
+        text
+    }
+
+    /// The notes wrapped to fit the terminal, padded to the same width so
+    /// they line up when centered.
+    fn notes_lines(&self, term_width: u16) -> Vec<String> {
+        let width = (term_width as usize).saturating_sub(4).min(70);
+
+        let lines: Vec<String> = self
+            .notes_text()
+            .lines()
+            .flat_map(|l| wrap(l, width))
+            .collect();
+        let longest = lines.iter().map(|l| text::width(l)).max().unwrap_or(0);
+
+        lines
+            .into_iter()
+            .map(|l| format!("{:<w$}", l, w = longest))
+            .collect()
+    }
+
+    /// How many lines of notes fit on the screen at once, leaving room for
+    /// the heading, the status bar and the line about going back.
+    fn notes_page(&self, term_height: u16) -> usize {
+        (term_height.saturating_sub(self.status_height() + 4) as usize).max(1)
+    }
+
+    /// Scroll the notes up or down by some lines, or close them on any other
+    /// key.
+    fn notes_key(&mut self, c: Key) {
+        let scroll = match self.overlay {
+            Some(Overlay::Notes(scroll)) => scroll,
+            _ => 0,
+        };
+        let (_, term_height) = self.terminal_size();
+        let page = self.notes_page(term_height);
+
+        let scroll = match c {
+            Key::Char('j') | Key::Down => scroll + 1,
+            Key::Char('k') | Key::Up => scroll.saturating_sub(1),
+            Key::PageDown | Key::Char(' ') => scroll + page,
+            Key::PageUp => scroll.saturating_sub(page),
+            _ => return self.close_overlay(),
+        };
+
+        self.overlay = Some(Overlay::Notes(scroll));
+        self.show_notes();
+    }
+
+    /// Show the notes, or say there aren't any.
+    fn open_notes(&mut self) {
+        if self.notes_text().trim().is_empty() {
+            self.show_message("This puzzle has no notes");
+            return;
+        }
+
+        self.overlay = Some(Overlay::Notes(0));
+        self.show_notes();
+    }
+
+    /// Put the grid back after the help or notes.
+    fn close_overlay(&mut self) {
+        self.overlay = None;
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+    }
+
+    fn confirm_reset(&mut self) {
+        self.confirm_reset = true;
+
+        self.draw_message_screen(&[
+            "Start Over?".into(),
+            "".into(),
+            "Every letter and mark will be cleared and the timer reset.".into(),
+            "Press y to start over, or any other key to go back.".into(),
+        ]);
+        self.stdout.flush().unwrap();
+    }
+
+    /// Clear the grid and the timer and carry on as if the puzzle had just
+    /// been opened. The checking aids are turned off, since the counts of
+    /// checks they added to start over too.
+    fn reset(&mut self) {
+        for cell in &mut self.grid {
+            cell.guess = None;
+            cell.marked_wrong = false;
+            cell.revealed = false;
+            cell.pencil = false;
+            cell.user_black = false;
+        }
+
+        self.stopwatch.reset();
+        self.earlier = Duration::from_secs(0);
+        self.paused = Duration::from_secs(0);
+        self.paused_since = None;
+        self.penalty = Duration::from_secs(0);
+        self.autosave_time = Duration::from_secs(0);
+        self.checks = 0;
+        self.reveals = 0;
+        self.hint_num_errors = false;
+        self.check_as_you_type = false;
+        self.history = History::default();
+        self.started = false;
+
+        self.unpause();
+    }
+
+    /// Show the title, author, copyright, size and notes of the puzzle, and
+    /// wait for a key.
+    fn intro(&mut self) {
+        self.mode = Mode::Intro;
+
+        let (term_width, term_height) = self.terminal_size();
+
+        let mut messages = vec![self.title.clone()];
+
+        if !self.author.is_empty() {
+            messages.push(format!("by {}", self.author));
+        }
+
+        if !self.copyright.is_empty() {
+            messages.push(self.copyright.clone());
+        }
+
+        messages.push(format!("{}x{}", self.width, self.height));
+
+        // As much of the notes as fits. The rest can be read with o later.
+
+        let room = (term_height.saturating_sub(self.status_height()) as usize)
+            .saturating_sub(messages.len() + 4);
+        let notes = self.notes_lines(term_width);
+
+        if !notes.is_empty() && room > 0 {
+            messages.push("".into());
+            messages.extend(notes.into_iter().take(room));
+        }
+
+        messages.push("".into());
+        messages.push("Press any key to start.".into());
+
+        self.draw_message_screen(&messages);
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    /// Leave the intro screen. The clock starts once the player does
+    /// something in the grid.
+    fn begin(&mut self) {
+        self.mode = Mode::Select;
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+
+        if self.resume_clock {
+            self.start_clock();
+        }
+
+        if self.start_solving {
+            self.start_solving();
+        }
+    }
+
+    fn unpause(&mut self) {
+        self.mode = Mode::Select;
+
+        if let Some(since) = self.paused_since.take() {
+            self.paused += since.elapsed();
+        }
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+
+        if self.started {
+            self.stopwatch.start();
+        }
+    }
+
+    fn toggle_hint_num_errors(&mut self) {
+        self.hint_num_errors = !self.hint_num_errors;
+
+        if self.hint_num_errors {
+            self.add_check_penalty();
+        }
+
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    /// Like the error count, turning this on counts as a check.
+    fn toggle_check_as_you_type(&mut self) {
+        self.check_as_you_type = !self.check_as_you_type;
+
+        if self.check_as_you_type {
+            self.add_check_penalty();
+        }
+
+        self.draw_all();
+        self.show_message(if self.check_as_you_type {
+            "Checking letters as they're typed"
+        } else {
+            "Not checking letters as they're typed"
+        });
+    }
+
+    fn toggle_skip_filled(&mut self) {
+        self.skip_filled = !self.skip_filled;
+
+        self.show_message(if self.skip_filled {
+            "Skipping filled cells"
+        } else {
+            "Not skipping filled cells"
+        });
+    }
+
+    fn toggle_auto_advance(&mut self) {
+        self.auto_advance = !self.auto_advance;
+
+        self.show_message(if self.auto_advance {
+            "Auto-advance on"
+        } else {
+            "Auto-advance off"
+        });
+    }
+
+    fn toggle_pencil_mode(&mut self) {
+        self.pencil_mode = !self.pencil_mode;
+
+        self.show_message(if self.pencil_mode {
+            "Pencil on"
+        } else {
+            "Pencil off"
+        });
+    }
+
+    fn toggle_wrap_navigation(&mut self) {
+        self.wrap_navigation = !self.wrap_navigation;
+
+        self.show_message(if self.wrap_navigation {
+            "Wrapping on"
+        } else {
+            "Wrapping off"
+        });
+    }
+
+    /// Whether the player can type into a cell. While the block pattern is
+    /// hidden, that's anything they haven't marked black themselves.
+    fn is_open(&self, x: u16, y: u16) -> bool {
+        if self.hide_blocks {
+            !self.get(x, y).user_black
+        } else {
+            self.get(x, y).truth.is_some()
+        }
+    }
+
+    /// Mark or unmark the cursor cell as black in a diagramless puzzle.
+    fn toggle_user_black(&mut self) {
+        if !self.hide_blocks {
+            return;
+        }
+
+        let x = self.cursor_x;
+        let y = self.cursor_y;
+
+        self.set_guess(x, y, None, false);
+
+        let cell = self.get_mut(x, y);
+        cell.user_black = !cell.user_black;
+
+        if cell.user_black {
+            self.select_mode();
+        }
+
+        self.draw_cell(x, y);
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    /// Show which cells are black in a diagramless puzzle, without giving away
+    /// any letters.
+    fn reveal_structure(&mut self) {
+        if !self.hide_blocks {
+            return;
+        }
+
+        self.hide_blocks = false;
+        self.structure_revealed = true;
+
+        self.add_reveal_penalty();
+
+        self.draw_all();
+        self.show_message("Block pattern revealed");
+    }
+
+    fn toggle_same_letter(&mut self) {
+        self.show_same_letter = !self.show_same_letter;
+
+        self.update_same_letter();
+        self.show_message(if self.show_same_letter {
+            "Showing matching letters"
+        } else {
+            "Not showing matching letters"
+        });
+    }
+
+    fn toggle_verbose_status(&mut self) {
+        self.verbose_status = !self.verbose_status;
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+    }
+
+    fn toggle_mono(&mut self) {
+        self.mono = !self.mono;
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+    }
+
+    fn toggle_overview(&mut self) {
+        self.overview = !self.overview;
+        self.drawn_cursor = None;
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+    }
+
+    fn toggle_numbers(&mut self) {
+        self.show_numbers = !self.show_numbers;
+
+        self.draw_all();
+        self.show_message(if self.show_numbers {
+            "Showing clue numbers"
+        } else {
+            "Hiding clue numbers"
+        });
+    }
+
+    fn toggle_ruler(&mut self) {
+        self.show_ruler = !self.show_ruler;
+
+        write!(self.stdout, "{}", clear::All).unwrap();
+
+        self.draw_all();
+    }
+
+    fn add_check_penalty(&mut self) {
+        self.checks += 1;
+        self.penalty += Duration::from_secs(self.config.check_penalty_secs);
+    }
+
+    fn add_reveal_penalty(&mut self) {
+        self.reveals += 1;
+        self.penalty += Duration::from_secs(self.config.reveal_penalty_secs);
+    }
+
+    /// Time on the clock, including any penalties.
+    fn elapsed(&self) -> Duration {
+        self.earlier + self.stopwatch.elapsed() + self.penalty
+    }
+
+    /// Put the puzzle title and how much of it is filled in into the terminal
+    /// window title, if that's changed since last time.
+    fn draw_terminal_title(&mut self) {
+        let s = self.get_status();
+        let percent = if s.cells == 0 {
+            100
+        } else {
+            (u32::from(s.guesses) * 100 / u32::from(s.cells)) as u16
+        };
+
+        if self.title_percent == Some(percent) {
+            return;
+        }
+
+        // Save the current title first so it can be restored on the way out.
+        if self.title_percent.is_none() {
+            write!(self.stdout, "\x1b[22;0t").unwrap();
+        }
+
+        self.title_percent = Some(percent);
+
+        let title: String = self.title.chars().filter(|c| !c.is_control()).collect();
+
+        write!(self.stdout, "\x1b]0;{} - {}%\x07", title, percent).unwrap();
+    }
+
+    /// Show a short message in the status bar for a few seconds
+    fn show_message(&mut self, message: &str) {
+        self.message = Some(message.to_string());
+        self.message_time = Instant::now();
+
+        self.draw_status_bar();
+        self.stdout.flush().unwrap();
+    }
+
+    fn start(&mut self) {
+        self.draw_terminal_title();
+
+        loop {
+            let input_at = self.last_input;
+
+            if !self.update() {
+                break;
+            }
+
+            let had_input = self.last_input != input_at;
+
+            match self.mode {
+                Mode::GameOver | Mode::Intro => {}
+                _ => {
+                    if !self.no_gameover && self.is_game_over() {
+                        self.finish();
+                    }
+                }
+            }
+
+            self.check_resize();
+
+            if self.message.is_some() && self.message_time.elapsed() > MESSAGE_TIME {
+                self.message = None;
+                self.shown_secs = None;
+            }
+
+            if self.autosave_secs > 0
+                && self.stopwatch.elapsed()
+                    >= self.autosave_time + Duration::from_secs(self.autosave_secs)
+            {
+                self.autosave_time = self.stopwatch.elapsed();
+                self.autosave(false);
+            }
+
+            // The status bar only needs drawing again when the clock ticks
+            // over to another second, or something may have changed it.
+
+            let secs = self.elapsed().as_secs();
+
+            if (had_input || self.shown_secs != Some(secs)) && !self.too_small {
+                self.shown_secs = Some(secs);
+
+                self.draw_terminal_title();
+                self.draw_status_bar();
+                self.draw_cursor();
+                self.stdout.flush().unwrap();
+            }
+
+            // Poll quickly while the player is typing, and back off while
+            // they're thinking so an idle game doesn't keep waking the CPU.
+
+            if self.last_input.elapsed() < Duration::from_secs(1) {
+                std::thread::sleep(Duration::from_millis(10));
+            } else {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+
+    fn update(&mut self) -> bool {
+        while let Some(b) = self.stdin.next() {
+            self.last_input = Instant::now();
+
+            if let Ok(Event::Mouse(m)) = b {
+                if !self.too_small {
+                    let playing = self.is_playing();
+
+                    self.handle_mouse(m);
+
+                    if playing {
+                        self.start_clock();
+                    }
+
+                    self.after_input();
+                    self.draw_cursor();
+                    self.stdout.flush().unwrap();
+                }
+            } else if let Ok(Event::Key(c)) = b {
+                // Nothing can be drawn while the grid doesn't fit, so only
+                // let the player quit.
+
+                let playing = self.is_playing();
+
+                if self.too_small {
+                    match (self.mode, c) {
+                        (Mode::Pause, _) | (Mode::GameOver, _) | (Mode::Intro, _) => {}
+                        (_, Key::Ctrl('c')) => return false,
+                        _ => continue,
+                    }
+                }
+
+                if !self.handle_key(c) {
+                    return false;
+                }
+
+                if playing {
+                    self.start_clock();
+                }
+
+                self.after_input();
+
+                self.draw_cursor();
+                self.stdout.flush().unwrap();
+            }
+        }
+
+        true
+    }
+
+    /// True while the grid is on screen to be played, rather than covered by
+    /// the intro, pause screen, help and so on.
+    fn is_playing(&self) -> bool {
+        match self.mode {
+            Mode::Select | Mode::EditAcross | Mode::EditDown => self.overlay.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Start the clock the first time the player does something in the grid,
+    /// as long as that didn't take them off it, e.g. by pausing.
+    fn start_clock(&mut self) {
+        if !self.started && self.is_playing() {
+            self.started = true;
+            self.stopwatch.start();
+        }
+    }
+
+    /// Bring the shading of matching letters up to date with wherever the
+    /// cursor and letters ended up, if the grid is on screen.
+    fn after_input(&mut self) {
+        match self.mode {
+            Mode::Select | Mode::EditAcross | Mode::EditDown if self.overlay.is_none() => {
+                self.update_same_letter()
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a single key press. Returns false if the game should end.
+    fn handle_key(&mut self, c: Key) -> bool {
+        use termion::event::Key::*;
+
+        match self.mode {
+            Mode::Intro => match c {
+                Ctrl('c') => return false,
+                _ => self.begin(),
+            },
+            Mode::Pause if self.confirm_reset => {
+                self.confirm_reset = false;
+
+                match c {
+                    Char('y') => self.reset(),
+                    _ => self.pause(),
+                }
+            }
+            Mode::Pause => match c {
+                Char('p') | Char('\n') | Esc => self.unpause(),
+                Char('r') => self.confirm_reset(),
+                Char('m') if self.from_menu => {
+                    self.back_to_menu = true;
+                    return false;
+                }
+                Ctrl('c') => return false,
+                _ => {}
+            },
+            Mode::Select | Mode::EditAcross | Mode::EditDown
+                if self.overlay == Some(Overlay::Help) =>
+            {
+                self.close_overlay()
+            }
+            Mode::Select | Mode::EditAcross | Mode::EditDown if self.overlay.is_some() => {
+                self.notes_key(c)
+            }
+            Mode::Select | Mode::EditAcross | Mode::EditDown if self.prompt.is_some() => {
+                self.prompt_key(c);
+            }
+            Mode::Select => match self.config.keys.normal.get(&c).cloned() {
+                Some(Action::ScrollUp) => self.clues_scroll_up(self.is_across()),
+                Some(Action::ScrollDown) => self.clues_scroll_down(self.is_across()),
+                Some(Action::MoveLeft) => self.select_move(Direction::Left),
+                Some(Action::MoveDown) => self.select_move(Direction::Down),
+                Some(Action::MoveUp) => self.select_move(Direction::Up),
+                Some(Action::MoveRight) => self.select_move(Direction::Right),
+                Some(Action::Pause) => self.pause(),
+                Some(Action::ToggleErrorCount) => self.toggle_hint_num_errors(),
+                Some(Action::ToggleCheckAsYouType) => self.toggle_check_as_you_type(),
+                Some(Action::ToggleWrap) => self.toggle_wrap_navigation(),
+                Some(Action::ToggleSkipFilled) => self.toggle_skip_filled(),
+                Some(Action::ToggleAutoAdvance) => self.toggle_auto_advance(),
+                Some(Action::ToggleRuler) => self.toggle_ruler(),
+                Some(Action::ToggleMono) => self.toggle_mono(),
+                Some(Action::ToggleSameLetter) => self.toggle_same_letter(),
+                Some(Action::ToggleVerboseStatus) => self.toggle_verbose_status(),
+                Some(Action::ToggleOverview) => self.toggle_overview(),
+                Some(Action::ToggleNumbers) => self.toggle_numbers(),
+                Some(Action::RevealStructure) => self.reveal_structure(),
+                Some(Action::MarkBlack) => self.toggle_user_black(),
+                Some(Action::FirstUnsolved) => self.first_unsolved(),
+                Some(Action::NextEmpty) => self.goto_next_empty(),
+                Some(Action::NextPencil) => self.goto_next_pencil(),
+                Some(Action::GotoClue) => self.open_prompt(Prompt::GotoClue),
+                Some(Action::Undo) => self.undo(),
+                Some(Action::TogglePencil) => self.toggle_pencil_mode(),
+                Some(Action::Redo) => self.redo(),
+                Some(Action::Save) => self.save_progress(),
+                Some(Action::CheckLetter) => self.check_letter(),
+                Some(Action::CheckWord) => self.check_word(),
+                Some(Action::CheckPuzzle) => self.check_puzzle(),
+                Some(Action::RevealLetter) => self.reveal_letter(),
+                Some(Action::RevealFirstLetter) => self.reveal_first_letter(),
+                Some(Action::RevealWord) => self.reveal_word(),
+                Some(Action::RevealPuzzle) => self.reveal_puzzle(),
+                Some(Action::Finish) if self.no_gameover => self.finish(),
+                Some(Action::EditMode) => self.edit_mode(),
+                Some(Action::Help) => self.show_help(),
+                Some(Action::Notes) => self.open_notes(),
+                _ => {}
+            },
+            Mode::EditAcross | Mode::EditDown => {
+                match (self.config.keys.edit.get(&c).cloned(), c) {
+                    (Some(Action::Rebus), _) => self.rebus_prompt(),
+                    (Some(Action::Erase), _) => self.unguess(),
+                    (Some(Action::ScrollUp), _) => self.clues_scroll_up(self.is_across()),
+                    (Some(Action::ScrollDown), _) => self.clues_scroll_down(self.is_across()),
+                    (Some(Action::Back), _) => self.backspace(),
+                    (Some(Action::MoveLeft), _) => self.edit_arrow(Direction::Left),
+                    (Some(Action::MoveDown), _) => self.edit_arrow(Direction::Down),
+                    (Some(Action::MoveUp), _) => self.edit_arrow(Direction::Up),
+                    (Some(Action::MoveRight), _) => self.edit_arrow(Direction::Right),
+                    (Some(Action::FirstUnsolved), _) => self.first_unsolved(),
+                    (Some(Action::NextEmpty), _) => self.goto_next_empty(),
+                    (Some(Action::NextPencil), _) => self.goto_next_pencil(),
+                    (Some(Action::NextClue), _) => self.next_clue(),
+                    (Some(Action::PrevClue), _) => self.prev_clue(),
+                    (Some(Action::MarkBlack), _) => self.toggle_user_black(),
+                    (Some(Action::Undo), _) => self.undo(),
+                    (Some(Action::TogglePencil), _) => self.toggle_pencil_mode(),
+                    (Some(Action::Redo), _) => self.redo(),
+                    (Some(Action::Save), _) => self.save_progress(),
+                    (Some(Action::CheckLetter), _) => self.check_letter(),
+                    (Some(Action::CheckWord), _) => self.check_word(),
+                    (Some(Action::RevealLetter), _) => self.reveal_letter(),
+                    (Some(Action::RevealFirstLetter), _) => self.reveal_first_letter(),
+                    (Some(Action::RevealWord), _) => self.reveal_word(),
+                    (Some(Action::NormalMode), _) => self.select_mode(),
+                    (Some(Action::Help), _) => self.show_help(),
+                    (Some(Action::Notes), _) => self.open_notes(),
+                    (Some(Action::Space), _) => match self.config.space_action {
+                        SpaceAction::ToggleDirection => self.edit_direction(),
+                        SpaceAction::SkipCell => self.edit_next(false),
+                    },
+                    (None, Char(c)) if c.is_alphanumeric() => {
+                        self.input(c);
+                    }
+                    _ => {}
+                }
+            }
+            Mode::GameOver => {
+                self.back_to_menu = self.from_menu;
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Hours, minutes and seconds, like `0:05:09`.
+fn format_time(secs: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        secs / 60 / 60,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// Break text into lines no longer than `width`, between words where
+/// possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+
+        if !line.is_empty() && text::width(&line) + 1 + word.len() > width {
+            lines.push(line);
+            line = String::new();
+        }
+
+        // A word too long for a line of its own gets split.
+
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(line);
+                line = String::new();
+            }
+
+            lines.push(word.drain(..width).collect());
+        }
+
+        if !word.is_empty() {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend(word);
+        }
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Spreadsheet-style column label: A through Z, then AA, AB, and so on.
+fn column_label(x: u16) -> String {
+    let mut label = Vec::new();
+    let mut n = x + 1;
+
+    while n > 0 {
+        n -= 1;
+        label.push((b'A' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+
+    label.iter().rev().collect()
+}
+
+/// Render a sample puzzle and time how long drawing and a burst of simulated
+/// keystrokes take, to get a feel for how fast the terminal is.
+fn selftest() {
+    let d = include_bytes!("../assets/test1.puz");
+    let p = match puzfile::parse(d) {
+        Ok(p) => p,
+        Err(e) => panic!("{}", e),
+    };
+
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let stdout = stdout.into_raw_mode().unwrap();
+
+    let mut g = Game::new(iter::empty(), stdout, &p, &Config::default());
+
+    let mut draw_times = Vec::new();
+
+    for _ in 0..50 {
+        let t = Instant::now();
+        g.draw_all();
+        draw_times.push(t.elapsed());
+    }
+
+    // Enter edit mode and type across the whole grid a few times, turning
+    // around at each row.
+
+    let mut keys = vec![Key::Char('i')];
+
+    for _ in 0..3 {
+        for _ in 0..g.height {
+            keys.extend(
+                "ABCDEFGHIJKLMNO"
+                    .chars()
+                    .take(g.width as usize)
+                    .map(Key::Char),
+            );
+            keys.push(Key::Down);
+            keys.extend((0..g.width).map(|_| Key::Left));
+        }
+        keys.extend((0..g.height).map(|_| Key::Up));
+    }
+
+    let mut key_times = Vec::new();
+
+    for key in keys {
+        let t = Instant::now();
+        g.handle_key(key);
+        g.draw_cursor();
+        g.stdout.flush().unwrap();
+        key_times.push(t.elapsed());
+    }
+
+    drop(g);
+
+    println!("draw_all   {}", timings(&mut draw_times));
+    println!("keystroke  {}", timings(&mut key_times));
+}
+
+/// Summarize a set of frame times as median / p95 in milliseconds.
+fn timings(samples: &mut [Duration]) -> String {
+    samples.sort();
+
+    let ms = |d: Duration| d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1_000_000.0;
+    let median = samples[samples.len() / 2];
+    let p95 = samples[(samples.len() * 95 / 100).min(samples.len() - 1)];
+
+    format!(
+        "n={} median={:.3}ms p95={:.3}ms",
+        samples.len(),
+        ms(median),
+        ms(p95)
+    )
+}
+
+fn main() {
+    let options = options::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("{}", options::USAGE);
+        ::std::process::exit(1);
+    });
+
+    if options.selftest {
+        selftest();
+        return;
+    }
+
+    let mut config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Could not read config: {}", e);
+        ::std::process::exit(1);
+    });
+
+    config.keys = KeyBindings::load(options.layout).unwrap_or_else(|e| {
+        eprintln!("Could not read key bindings: {}", e);
+        ::std::process::exit(1);
+    });
+
+    if let Some(ref path) = options.theme {
+        config.theme = Theme::load(path).unwrap_or_else(|e| {
+            eprintln!("Could not read theme: {}", e);
+            ::std::process::exit(1);
+        });
+    }
+
+    if let Some(ref dir) = options.dir {
+        play_dir(dir, &config, &options);
+        return;
+    }
+
+    let filename = options.filename.clone().unwrap_or_else(|| {
+        eprintln!("{}", options::USAGE);
+        ::std::process::exit(1);
+    });
+
+    if filename.to_lowercase().ends_with(".zip") {
+        play_pack(&filename, &config, &options);
+        return;
+    }
+
+    // A puzzle piped in on stdin is read all at once up front. Keys come from
+    // the terminal itself either way, as `async_stdin` reads /dev/tty.
+
+    let from_stdin = filename == "-";
+    let mut v = Vec::new();
+
+    let read = if from_stdin {
+        io::stdin().read_to_end(&mut v)
+    } else {
+        File::open(&filename).and_then(|mut f| f.read_to_end(&mut v))
+    };
+
+    if let Err(e) = read {
+        eprintln!("Could not read {}: {}", filename, e);
+        ::std::process::exit(1);
+    }
+
+    let mut p = load(&v[..], options.format).unwrap_or_else(|e| {
+        eprintln!("Could not parse {}: {}", filename, e);
+        ::std::process::exit(1);
+    });
+
+    if p.is_puz() {
+        if let Err(e) = p.verify_checksums() {
+            eprintln!("Warning: {} may be damaged: {}", filename, e);
+        }
+
+        if p.missing_clues > 0 {
+            eprintln!(
+                "Warning: {} is missing {} of its clues",
+                filename, p.missing_clues
+            );
+        }
+    }
+
+    if options.info {
+        print_info(&p);
+        return;
+    }
+
+    if p.is_scrambled() {
+        let result = match options.key {
+            Some(key) => p.unscramble(key),
+            None => {
+                eprintln!("This puzzle is locked. Give its four digit key with --key.");
+                ::std::process::exit(1);
+            }
+        };
+
+        if result.is_err() {
+            eprintln!("That key doesn't unlock this puzzle.");
+            ::std::process::exit(1);
+        }
+    }
+
+    if options.export_text {
+        let g = Game::new(iter::empty(), io::sink(), &p, &config);
+        print!("{}", g.export_text());
+        return;
+    }
+
+    if let Some(ref path) = options.export_svg {
+        if let Err(e) = fs::write(path, svg::render(&p, options.solution)) {
+            eprintln!("Could not write {}: {}", path, e);
+            ::std::process::exit(1);
+        }
+
+        return;
+    }
+
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let stdout = MouseTerminal::from(stdout.into_raw_mode().unwrap());
+
+    let stdin = async_stdin();
+
+    init(
+        stdin,
+        stdout,
+        &p,
+        &config,
+        &options,
+        Some(Path::new(&filename)).filter(|_| !from_stdin),
+        false,
+    );
+}
+
+/// Describe a puzzle without playing it.
+fn print_info(p: &PuzFile) {
+    let yes_no = |b| if b { "yes" } else { "no" };
+
+    println!("Title:       {}", p.title);
+    println!("Author:      {}", p.author);
+    println!("Copyright:   {}", p.copyright);
+    println!("Size:        {}x{}", p.width, p.height);
+    println!("Clues:       {}", p.clues.len());
+    println!("Locked:      {}", yes_no(p.is_scrambled()));
+    println!("Diagramless: {}", yes_no(p.is_diagramless()));
+    println!(
+        "Rebus:       {}",
+        yes_no(p.rebus.iter().any(Option::is_some))
+    );
+
+    if !p.notes.is_empty() {
+        println!("Notes:       {}", p.notes);
+    }
+}
+
+/// Parse a puzzle in the given format, or work out the format from the data.
+fn load(data: &[u8], format: Option<Format>) -> Result<PuzFile, String> {
+    let format = format.unwrap_or_else(|| {
+        if nyt::detect(data) {
+            Format::Nyt
+        } else {
+            Format::Puz
+        }
+    });
+
+    let p = match format {
+        Format::Puz => puzfile::parse(data).map_err(|e| e.to_string())?,
+        Format::Nyt => nyt::parse(data)?,
+    };
+
+    p.verify_clues().map_err(|e| e.to_string())?;
+    check_size(p.width as usize, p.height as usize)?;
+
+    Ok(p)
+}
+
+/// Where to scroll a line of `total` cells, `shown` of which fit on screen, so
+/// the cursor stays in view with a cell to spare beside it where there's room.
+fn scroll_to(scroll: u16, cursor: u16, shown: u16, total: u16) -> u16 {
+    if shown == 0 || shown >= total {
+        return 0;
+    }
+
+    let spare = if shown >= 3 { 1 } else { 0 };
+
+    let scroll = if cursor < scroll + spare {
+        cursor.saturating_sub(spare)
+    } else if cursor + spare >= scroll + shown {
+        cursor + spare + 1 - shown
+    } else {
+        scroll
+    };
+
+    scroll.min(total - shown)
+}
+
+/// Refuse grids without any squares, which can't be played. Grids bigger
+/// than the terminal scroll, and the most a .puz file can hold, 255x255, is
+/// well within reach of 16 bit terminal coordinates.
+fn check_size(width: usize, height: usize) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!("the grid is {}x{}, with no squares", width, height));
+    }
+
+    Ok(())
+}
+
+/// Let the player pick puzzles out of a zip archive until they quit.
+fn play_pack(filename: &str, config: &Config, options: &Options) {
+    let puzzles = pack::read_zip(Path::new(filename)).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", filename, e);
+        ::std::process::exit(1);
+    });
+
+    if puzzles.is_empty() {
+        eprintln!("No .puz files found in {}", filename);
+        ::std::process::exit(1);
+    }
+
+    let names: Vec<String> = puzzles.iter().map(|p| p.0.clone()).collect();
+
+    play_menu(
+        filename,
+        || names.clone(),
+        config,
+        options,
+        |i| {
+            let p = load(&puzzles[i].1[..], Some(Format::Puz))?;
+
+            Ok((p, None))
+        },
+    );
+}
+
+/// Let the player pick puzzles from the .puz files in a directory until they
+/// quit. Progress is saved back into each file as it's played.
+fn play_dir(dir: &str, config: &Config, options: &Options) {
+    let paths = pack::read_dir(Path::new(dir)).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", dir, e);
+        ::std::process::exit(1);
+    });
+
+    if paths.is_empty() {
+        eprintln!("No .puz files found in {}", dir);
+        ::std::process::exit(1);
+    }
+
+    let open = |path: &Path| {
+        let data = fs::read(path).map_err(|e| e.to_string())?;
+        load(&data, Some(Format::Puz))
+    };
+
+    // List each puzzle with its title and author, so they're easier to tell
+    // apart than by file name alone.
+
+    // Solved puzzles get a check mark and their best time. The names are
+    // built again each time the menu comes back, in case one was just solved.
+
+    let names = || {
+        let records = records::path()
+            .map(|path| Records::load(&path))
+            .unwrap_or_default();
+
+        paths
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+                let p = match open(path) {
+                    Ok(p) => p,
+                    Err(_) => return format!("  {}  (unreadable)", name),
+                };
+
+                let best = records.best(records::key(&p));
+
+                let mut s = format!(
+                    "{} {}  {}",
+                    if best.is_some() { '\u{2713}' } else { ' ' },
+                    name,
+                    p.title
+                );
+
+                if !p.author.is_empty() {
+                    s.push_str(&format!(" by {}", p.author));
+                }
+
+                if let Some(secs) = best {
+                    s.push_str(&format!("  ({})", format_time(secs)));
+                }
+
+                s
+            })
+            .collect()
+    };
+
+    play_menu(dir, names, config, options, |i| {
+        let p = open(&paths[i])?;
+
+        Ok((p, Some(paths[i].clone())))
+    });
+}
+
+/// Show a menu of puzzles until the player quits, playing each one they pick
+/// and coming back to the menu when it's over. `open` reads the puzzle at an
+/// index into `names`, along with the file to save progress into, if any.
+fn play_menu<N, F>(title: &str, names: N, config: &Config, options: &Options, open: F)
+where
+    N: Fn() -> Vec<String>,
+    F: Fn(usize) -> Result<(PuzFile, Option<PathBuf>), String>,
+{
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+    let mut stdout = MouseTerminal::from(stdout.into_raw_mode().unwrap());
+
+    let mut stdin = async_stdin();
+
+    let mut selected = 0;
+    let mut message = "enter: open  q: quit".to_string();
+
+    loop {
+        let names = names();
+
+        // Each game shows the cursor again as it ends.
+        write!(stdout, "{}", cursor::Hide).unwrap();
+
+        let i = match menu::select(
+            &mut (&mut stdin).keys(),
+            &mut stdout,
+            &config.keys.normal,
+            title,
+            &names,
+            selected,
+            &message,
+        ) {
+            Some(i) => i,
+            None => break,
+        };
+
+        selected = i;
+
+        let (mut p, path) = match open(i) {
+            Ok(puzzle) => puzzle,
+            Err(e) => {
+                message = format!("Could not parse {}: {}", names[i], e);
+                continue;
+            }
+        };
+
+        if p.is_scrambled() {
+            match options.key.map(|key| p.unscramble(key)) {
+                Some(Ok(())) => {}
+                _ => {
+                    message = format!("{} is locked. Give its key with --key.", names[i]);
+                    continue;
+                }
+            }
+        }
+
+        message = "enter: open  q: quit".to_string();
+
+        let path = path.as_deref();
+
+        if !init(&mut stdin, &mut stdout, &p, config, options, path, true) {
+            break;
+        }
+    }
+
+    write!(
+        stdout,
+        "{}{}{}",
+        clear::All,
+        cursor::Goto(1, 1),
+        cursor::Show
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    type Events = vec::IntoIter<Result<Event, io::Error>>;
+
+    fn events(keys: &[Key]) -> Events {
+        let events: Vec<_> = keys.iter().map(|&k| Ok(Event::Key(k))).collect();
+
+        events.into_iter()
+    }
+
+    /// Typing each letter of `s`.
+    fn typing(s: &str) -> Vec<Key> {
+        s.chars().map(Key::Char).collect()
+    }
+
+    /// A game of test2.puz drawn into memory as if into an 80x24 terminal,
+    /// with `keys` waiting to be read.
+    fn game(keys: &[Key]) -> Game<Events, Vec<u8>> {
+        let p = puzfile::parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        let mut g = Game::new(events(keys), Vec::new(), &p, &Config::default());
+        g.term_size = (80, 24);
+        g.draw_all();
+
+        g
+    }
+
+    #[test]
+    fn unexpected_cells() {
+        let words = |p: &PuzFile| {
+            let g = Game::new(events(&[]), Vec::new(), p, &Config::default());
+
+            g.words
+                .iter()
+                .map(|w| (w.x, w.y, w.across))
+                .collect::<Vec<_>>()
+        };
+
+        let p = puzfile::parse(include_bytes!("../assets/test2.puz")).unwrap();
+        let split = puzfile::parse(include_bytes!("../assets/test7.puz")).unwrap();
+
+        assert_eq!(words(&split), words(&p));
+
+        let g = Game::new(events(&[]), Vec::new(), &split, &Config::default());
+
+        assert!(g.grid[4].truth.is_none());
+        assert!(g.notes_text().contains("Unexpected ':' in 1 square"));
+    }
+
+    #[test]
+    fn select_move() {
+        let mut g = game(&[]);
+        g.wrap_navigation = false;
+
+        g.select_move(Direction::Right);
+        g.select_move(Direction::Down);
+
+        // Black squares can be moved onto, just not edited.
+
+        assert_eq!(g.cursor(), (1, 1));
+
+        g.select_move(Direction::Right);
+        g.select_move(Direction::Right);
+
+        assert_eq!(g.cursor(), (2, 1));
+
+        g.wrap_navigation = true;
+        g.select_move(Direction::Right);
+
+        assert_eq!(g.cursor(), (0, 1));
+    }
+
+    #[test]
+    fn edit_next() {
+        let mut g = game(&[]);
+
+        g.edit_mode();
+        g.edit_next(false);
+        g.edit_next(false);
+
+        assert_eq!(g.cursor(), (2, 0));
+
+        // The end of a word is as far as it goes.
+
+        g.edit_next(false);
+
+        assert_eq!(g.cursor(), (2, 0));
+
+        g.cursor_x = 0;
+        g.mode = Mode::EditDown;
+        g.edit_next(false);
+
+        assert_eq!(g.cursor(), (0, 1));
+        assert_eq!(g.cell_guess(0, 1), None);
+    }
+
+    #[test]
+    fn solve() {
+        let mut g = game(&[Key::Char('x'), Key::Char('\n')]);
+        g.mode = Mode::Intro;
+        g.auto_advance = true;
+
+        // Any key gets past the intro, then enter starts editing across.
+
+        assert!(g.update());
+        assert_eq!(g.mode, Mode::EditAcross);
+        assert_eq!(g.cursor(), (0, 0));
+
+        // Filling a word moves on to the next one that has room left.
+
+        g.stdin = events(&typing("puz"));
+
+        assert!(g.update());
+        assert_eq!(g.cell_guess(0, 0), Some("P"));
+        assert_eq!(g.cell_guess(2, 0), Some("Z"));
+        assert_eq!(g.cursor(), (0, 2));
+        assert!(!g.is_game_over());
+
+        g.stdin = events(&typing("pooox"));
+
+        assert!(g.update());
+        assert_eq!(g.mode, Mode::EditDown);
+        assert_eq!(g.cursor(), (2, 2));
+        assert_eq!(g.cell_guess(2, 1), Some("X"));
+        assert!(!g.is_game_over());
+
+        // Fixing the wrong letter solves it.
+
+        g.stdin = events(&[Key::Esc, Key::Up, Key::Char('\n'), Key::Char('o')]);
+
+        assert!(g.update());
+        assert!(g.is_game_over());
+
+        g.finish();
+
+        assert_eq!(g.mode, Mode::GameOver);
+
+        // Any key ends a finished game.
+
+        g.stdin = events(&[Key::Char('q')]);
+
+        assert!(!g.update());
+    }
+
+    #[test]
+    fn select_mode_keys() {
+        let mut g = game(&[
+            Key::Char('l'),
+            Key::Char('j'),
+            Key::Char('\n'),
+            Key::Char('j'),
+            Key::Char('l'),
+            Key::Right,
+        ]);
+
+        // In select mode, letters move the cursor rather than being typed.
+        // The black square can be stood on but not edited, and moving off the
+        // edge wraps around.
+
+        assert!(g.update());
+        assert_eq!(g.mode, Mode::Select);
+        assert_eq!(g.cursor(), (0, 2));
+        assert_eq!(g.cell_guess(1, 0), None);
+
+        g.stdin = events(&[Key::Up, Key::Char('\n'), Key::Char('z'), Key::Esc]);
+
+        assert!(g.update());
+        assert_eq!(g.mode, Mode::Select);
+        assert_eq!(g.cell_guess(0, 1), Some("Z"));
+    }
+
+    #[test]
+    fn crossing() {
+        let mut g = game(&[]);
+        g.config.post_word_flow = PostWordFlow::Crossing;
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&typing("puz"));
+
+        assert!(g.update());
+        assert_eq!(g.mode, Mode::EditDown);
+        assert_eq!(g.cursor(), (2, 1));
+
+        // Correcting a letter of a word that was already full carries on
+        // along it rather than switching direction again.
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&typing("q"));
+
+        assert!(g.update());
+        assert_eq!(g.mode, Mode::EditAcross);
+        assert_eq!(g.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn auto_advance() {
+        let mut g = game(&[]);
+        g.auto_advance = true;
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&typing("puz"));
+
+        assert!(g.update());
+        assert_eq!(g.cursor(), (0, 2));
+
+        // Letters in a word that was already full can be corrected in turn.
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&typing("qu"));
+
+        assert!(g.update());
+        assert_eq!(g.cursor(), (2, 0));
+        assert_eq!(g.cell_guess(0, 0), Some("Q"));
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut g = game(&[]);
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&[
+            Key::Char('c'),
+            Key::Char('a'),
+            Key::Char('t'),
+            Key::Ctrl('z'),
+            Key::Ctrl('z'),
+        ]);
+
+        assert!(g.update());
+        assert_eq!(g.cell_guess(0, 0), Some("C"));
+        assert_eq!(g.cell_guess(1, 0), None);
+        assert_eq!(g.cell_guess(2, 0), None);
+        assert_eq!(g.cursor(), (1, 0));
+
+        g.stdin = events(&[Key::Ctrl('y')]);
+
+        assert!(g.update());
+        assert_eq!(g.cell_guess(1, 0), Some("A"));
+        assert_eq!(g.cursor(), (1, 0));
+
+        // Typing something new means what was undone can't be redone.
+
+        g.stdin = events(&[Key::Char('x'), Key::Ctrl('y')]);
+
+        assert!(g.update());
+        assert_eq!(g.cell_guess(1, 0), Some("X"));
+        assert_eq!(g.cell_guess(2, 0), None);
+
+        for _ in 0..3 {
+            g.undo();
+        }
+
+        assert_eq!(g.cell_guess(0, 0), None);
+        assert_eq!(g.cell_guess(1, 0), None);
+    }
+
+    #[test]
+    fn undo_pencil() {
+        let mut g = game(&[]);
+        g.pencil_mode = true;
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&[Key::Char('p'), Key::Ctrl('z'), Key::Ctrl('y')]);
+
+        assert!(g.update());
+        assert_eq!(g.cell_guess(0, 0), Some("P"));
+        assert!(g.get(0, 0).pencil);
+
+        // Inking over a pencilled letter can be undone too, even when it's the
+        // same letter.
+
+        g.pencil_mode = false;
+        g.edit_at(0, 0, true);
+        g.stdin = events(&[Key::Char('p')]);
+
+        assert!(g.update());
+        assert!(!g.get(0, 0).pencil);
+
+        g.undo();
+
+        assert!(g.get(0, 0).pencil);
+    }
+
+    #[test]
+    fn reset() {
+        let mut g = game(&[]);
+
+        g.toggle_check_as_you_type();
+        g.toggle_hint_num_errors();
+        g.grid[4].user_black = true;
+        g.edit_guess(0, 0, Some("P".into()), false);
+
+        assert_eq!(g.checks, 2);
+
+        g.reset();
+
+        assert_eq!(g.checks, 0);
+        assert!(!g.check_as_you_type);
+        assert!(!g.hint_num_errors);
+        assert!(!g.grid[4].user_black);
+        assert_eq!(g.cell_guess(0, 0), None);
+        assert!(g.solved_cleanly());
+    }
+
+    #[test]
+    fn scroll_clues() {
+        let mut g = game(&[]);
+        g.term_size = (80, 6);
+
+        for _ in 0..10 {
+            g.clues_scroll_down(true);
+        }
+
+        let lines = g.clue_columns()[0].1.len() as u16;
+        let max = lines - (6 - g.status_height());
+
+        assert!(max > 0);
+        assert_eq!(g.clues_scroll, max);
+
+        g.clues_scroll_up(true);
+
+        assert_eq!(g.clues_scroll, max.saturating_sub(5));
+
+        // Clicking below everything there is doesn't overflow.
+
+        g.clues_scroll = u16::MAX;
+        let x = g.clues_left();
+        g.click_clue(x, 1);
+    }
+
+    #[test]
+    fn undo_reveal() {
+        let mut g = game(&[]);
+        g.pencil_mode = true;
+
+        g.edit_at(0, 0, true);
+        g.stdin = events(&[Key::Char('x'), Key::Char('a')]);
+
+        assert!(g.update());
+
+        g.reveal_cells(&[(0, 0)]);
+
+        assert!(!g.get(0, 0).pencil);
+
+        g.undo();
+
+        assert_eq!(g.cell_guess(0, 0), Some("P"));
+        assert_eq!(g.cell_guess(1, 0), None);
+
+        g.undo();
+
+        assert_eq!(g.cell_guess(0, 0), Some("P"));
+    }
+
+    #[test]
+    fn help_is_remembered() {
+        let path = env::temp_dir().join("puzterm-help-is-remembered.puzterm");
+
+        let mut g = game(&[]);
+        g.sidecar_path = Some(path.clone());
+        g.checks = 2;
+        g.save_sidecar();
+
+        let mut g = game(&[]);
+        g.sidecar_path = Some(path.clone());
+        g.load_sidecar();
+
+        assert_eq!(g.checks, 2);
+        assert!(!g.solved_cleanly());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn export_text() {
+        let mut g = game(&[]);
+
+        assert!(g.export_text().contains("\n---\n-.-\n---\n"));
+
+        g.hide_blocks = true;
+
+        assert!(g.export_text().contains("\n---\n---\n---\n"));
+
+        g.get_mut(2, 2).user_black = true;
+
+        assert!(g.export_text().contains("\n---\n---\n--.\n"));
+    }
+
+    #[test]
+    fn save_truncated() {
+        let path = env::temp_dir().join("puzterm-save-truncated.puz");
+        let d = &include_bytes!("../assets/test2.puz")[..170];
+        fs::write(&path, d).unwrap();
+
+        let p = puzfile::parse(d).unwrap();
+        let mut g = Game::new(events(&[]), Vec::new(), &p, &Config::default());
+        g.edit_guess(0, 0, Some("P".into()), false);
+
+        assert!(g.save(&path).is_err());
+
+        let saved = fs::read(&path).unwrap();
+
+        assert_eq!(saved, d);
+        assert!(puzfile::parse(&saved).is_ok());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn autosave() {
+        let path = env::temp_dir().join("puzterm-autosave.puz");
+        let d = include_bytes!("../assets/test2.puz");
+        fs::write(&path, &d[..]).unwrap();
+
+        let p = puzfile::parse(d).unwrap();
+        let mut g = Game::new(events(&[]), Vec::new(), &p, &Config::default());
+        g.puz_path = Some(path.clone());
+        g.saved_state = g.state();
+        g.saved_secs = 60;
+
+        // Only the clock has moved on, which waits until the game ends.
+
+        g.autosave(false);
+        assert_eq!(g.saved_secs, 60);
+
+        g.autosave(true);
+        assert_eq!(g.saved_secs, 0);
+
+        g.edit_guess(0, 0, Some("P".into()), false);
+        g.autosave(false);
+
+        assert_eq!(g.saved_state, g.state());
+        assert_eq!(
+            puzfile::parse(&fs::read(&path).unwrap()).unwrap().state,
+            g.state()
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_grid() {
+        assert!(check_size(0, 0).is_err());
+        assert!(check_size(15, 0).is_err());
+        assert_eq!(check_size(255, 255), Ok(()));
+    }
 }
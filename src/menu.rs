@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use termion::event::Key;
+use termion::{clear, cursor, style};
+
+use keys::Action;
+use text;
+
+/// Show a list of items and let the player pick one with the keys `keys`
+/// binds to moving up and down.
+///
+/// Returns the index of the chosen item, or `None` if the player quit.
+pub fn select<R, W>(
+    stdin: &mut R,
+    stdout: &mut W,
+    keys: &HashMap<Key, Action>,
+    title: &str,
+    items: &[String],
+    mut selected: usize,
+    message: &str,
+) -> Option<usize>
+where
+    R: Iterator<Item = Result<Key, io::Error>>,
+    W: Write,
+{
+    let mut scroll = 0;
+
+    draw(stdout, title, items, selected, &mut scroll, message);
+
+    loop {
+        for c in stdin.by_ref().flatten() {
+            use termion::event::Key::*;
+
+            match (keys.get(&c), c) {
+                (Some(Action::MoveUp), _) => {
+                    selected = if selected == 0 {
+                        items.len() - 1
+                    } else {
+                        selected - 1
+                    }
+                }
+                (Some(Action::MoveDown), _) => {
+                    selected = if selected + 1 == items.len() {
+                        0
+                    } else {
+                        selected + 1
+                    }
+                }
+                (_, Char('\n')) | (_, Char('i')) => return Some(selected),
+                (_, Char('q')) | (_, Ctrl('c')) | (_, Esc) => return None,
+                _ => {}
+            }
+
+            draw(stdout, title, items, selected, &mut scroll, message);
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn draw<W: Write>(
+    stdout: &mut W,
+    title: &str,
+    items: &[String],
+    selected: usize,
+    scroll: &mut usize,
+    message: &str,
+) {
+    let (term_width, term_height) = termion::terminal_size().unwrap();
+
+    // Leave room for the title, a blank line, and the message at the bottom.
+
+    let list_height = term_height.saturating_sub(4).max(1) as usize;
+
+    if selected < *scroll {
+        *scroll = selected;
+    } else if selected >= *scroll + list_height {
+        *scroll = selected + 1 - list_height;
+    }
+
+    write!(
+        stdout,
+        "{}{}{}{}{}",
+        clear::All,
+        cursor::Goto(1, 1),
+        style::Bold,
+        title,
+        style::Reset
+    )
+    .unwrap();
+
+    for (i, item) in items.iter().enumerate().skip(*scroll).take(list_height) {
+        let item = text::strip_controls(item);
+        let line = text::truncate(&format!(" {} ", item), term_width as usize);
+
+        write!(stdout, "{}", cursor::Goto(1, (i - *scroll) as u16 + 3)).unwrap();
+
+        if i == selected {
+            write!(stdout, "{}{}{}", style::Invert, line, style::Reset).unwrap();
+        } else {
+            write!(stdout, "{}", line).unwrap();
+        }
+    }
+
+    write!(stdout, "{}{}", cursor::Goto(1, term_height), message).unwrap();
+
+    stdout.flush().unwrap();
+}
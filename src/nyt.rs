@@ -0,0 +1,189 @@
+use serde::Deserialize;
+use serde_json;
+
+use puzfile::{self, PuzFile};
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Puzzle {
+    title: Option<String>,
+    publication_date: Option<String>,
+    constructors: Vec<String>,
+    copyright: Option<String>,
+    notes: Vec<Note>,
+    body: Vec<Body>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Note {
+    text: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Body {
+    cells: Vec<Cell>,
+    clues: Vec<Clue>,
+    dimensions: Dimensions,
+}
+
+/// Black cells are empty objects, so everything here is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Cell {
+    answer: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Clue {
+    direction: String,
+    label: String,
+    text: Vec<ClueText>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ClueText {
+    plain: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Dimensions {
+    width: u8,
+    height: u8,
+}
+
+/// Does this look like a New York Times puzzle rather than a .puz file?
+pub fn detect(data: &[u8]) -> bool {
+    data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+}
+
+/// Read a daily crossword in the JSON format the New York Times serves.
+pub fn parse(data: &[u8]) -> Result<PuzFile, String> {
+    let puzzle: Puzzle = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+
+    let body = match puzzle.body.into_iter().next() {
+        Some(body) => body,
+        None => return Err("no puzzle body".into()),
+    };
+
+    let width = body.dimensions.width;
+    let height = body.dimensions.height;
+
+    if body.cells.len() != width as usize * height as usize {
+        return Err(format!(
+            "expected {} cells for a {}x{} grid, found {}",
+            width as usize * height as usize,
+            width,
+            height,
+            body.cells.len()
+        ));
+    }
+
+    // The grid only has room for the first letter of a rebus answer. The rest
+    // goes in `rebus`.
+
+    let puzzle_grid: String = body
+        .cells
+        .iter()
+        .map(|c| match c.answer {
+            Some(ref a) => a.chars().next().unwrap_or('.'),
+            None => '.',
+        })
+        .collect();
+    let state: String = puzzle_grid
+        .chars()
+        .map(|c| if c == '.' { '.' } else { '-' })
+        .collect();
+
+    let mut across = Vec::new();
+    let mut down = Vec::new();
+
+    for clue in &body.clues {
+        let number = match clue.label.parse() {
+            Ok(n) => n,
+            Err(_) => return Err(format!("bad clue number: {:?}", clue.label)),
+        };
+        let text = clue
+            .text
+            .iter()
+            .map(|t| t.plain.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if clue.direction == "Across" {
+            across.push((number, text));
+        } else {
+            down.push((number, text));
+        }
+    }
+
+    let clues = puzfile::clues_in_scan_order(&across, &down, &puzzle_grid, width);
+
+    let title = match (puzzle.title, puzzle.publication_date) {
+        (Some(title), _) => title,
+        (None, Some(date)) => format!("NYT Crossword {}", date),
+        (None, None) => "NYT Crossword".into(),
+    };
+
+    Ok(PuzFile {
+        width,
+        height,
+        num_clues: clues.len() as u16,
+        puzzle: puzzle_grid,
+        state,
+        title,
+        author: puzzle.constructors.join(", "),
+        copyright: puzzle.copyright.unwrap_or_default(),
+        clues,
+        notes: puzzle
+            .notes
+            .iter()
+            .map(|n| n.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        rebus: body
+            .cells
+            .iter()
+            .map(|c| match c.answer {
+                Some(ref a) if a.chars().count() > 1 => Some(a.clone()),
+                _ => None,
+            })
+            .collect(),
+        ..PuzFile::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small() {
+        let d = include_bytes!("../assets/test5.json");
+
+        assert!(detect(d));
+        assert!(!detect(include_bytes!("../assets/test2.puz")));
+
+        let p = parse(d).unwrap();
+
+        assert_eq!(p.width, 3);
+        assert_eq!(p.height, 3);
+        assert_eq!(p.puzzle, concat!("PUZ", "O.O", "POO"));
+        assert_eq!(p.title, "Test: Test Test Test");
+        assert_eq!(p.author, "Test, Test Again");
+        assert_eq!(p.num_clues, 4);
+        assert_eq!(
+            p.clues,
+            vec![
+                "The file extension for Across Lite puzzle files.",
+                "A fizzy drink, if you're from Minnesota.",
+                "A big place with lots of animals.",
+                "A projectile for some primates.",
+            ]
+        );
+    }
+}
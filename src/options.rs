@@ -0,0 +1,246 @@
+use keys::Layout;
+
+pub const USAGE: &str =
+    "Usage: puzterm [--no-gameover] [--format puz|nyt] [--autosave-secs <n>] [--key <nnnn>] [--theme <file>] [--layout qwerty|dvorak|colemak] [--mono] [--compact] [--overview] [--center] [--start-solving] [--info] [--export-text] [--export-svg <out.svg> [--solution]] <file>\n       puzterm [options] --dir <directory>";
+
+/// Puzzle file formats that can be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Puz,
+    /// The JSON the New York Times serves its puzzles in.
+    Nyt,
+}
+
+/// Command line options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub filename: Option<String>,
+    /// Read the file as this format instead of guessing.
+    pub format: Option<Format>,
+    /// The four digit key that unlocks a scrambled puzzle.
+    pub key: Option<u16>,
+    /// A file of colors to use instead of the usual ones.
+    pub theme: Option<String>,
+    /// How often progress is saved while playing. Zero turns autosave off.
+    pub autosave_secs: u64,
+    /// Pick puzzles from the .puz files in this directory.
+    pub dir: Option<String>,
+    /// Which letters move around in normal mode.
+    pub layout: Layout,
+    /// Draw without colors.
+    pub mono: bool,
+    /// Draw each cell two columns wide and two rows tall, without clue
+    /// numbers, so bigger grids fit.
+    pub compact: bool,
+    /// Start out showing the grid one character per cell.
+    pub overview: bool,
+    /// Center the game in the terminal.
+    pub center: bool,
+    /// Start out editing the first across clue.
+    pub start_solving: bool,
+    /// Print what's known about the puzzle instead of playing it.
+    pub info: bool,
+    /// Print the grid and clues as text instead of playing.
+    pub export_text: bool,
+    /// Draw the grid into this SVG file instead of playing.
+    pub export_svg: Option<String>,
+    /// Export the answers rather than the letters filled in so far.
+    pub solution: bool,
+    /// Don't end the game when the grid is filled in correctly.
+    pub no_gameover: bool,
+    /// Time drawing and input handling instead of playing. Not advertised.
+    pub selftest: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            filename: None,
+            format: None,
+            key: None,
+            theme: None,
+            autosave_secs: 30,
+            dir: None,
+            layout: Layout::Qwerty,
+            mono: false,
+            compact: false,
+            overview: false,
+            center: false,
+            start_solving: false,
+            info: false,
+            export_text: false,
+            export_svg: None,
+            solution: false,
+            no_gameover: false,
+            selftest: false,
+        }
+    }
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("puz") => Some(Format::Puz),
+                    Some("nyt") => Some(Format::Nyt),
+                    _ => return Err("--format must be puz or nyt".into()),
+                }
+            }
+            "--autosave-secs" => {
+                options.autosave_secs = match args.next().map(|s| s.parse()) {
+                    Some(Ok(secs)) => secs,
+                    _ => return Err("--autosave-secs must be a number of seconds".into()),
+                }
+            }
+            "--key" => {
+                options.key = match args.next().map(|s| s.parse()) {
+                    Some(Ok(key)) if key <= 9999 => Some(key),
+                    _ => return Err("--key must be a four digit number".into()),
+                }
+            }
+            "--theme" => {
+                options.theme = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err("--theme needs a file".into()),
+                }
+            }
+            "--dir" => {
+                options.dir = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err("--dir needs a directory".into()),
+                }
+            }
+            "--layout" => {
+                options.layout = match args.next().as_deref().and_then(Layout::parse) {
+                    Some(layout) => layout,
+                    None => return Err("--layout must be qwerty, dvorak or colemak".into()),
+                }
+            }
+            "--mono" => options.mono = true,
+            "--compact" => options.compact = true,
+            "--overview" => options.overview = true,
+            "--center" => options.center = true,
+            "--start-solving" => options.start_solving = true,
+            "--info" => options.info = true,
+            "--export-text" => options.export_text = true,
+            "--export-svg" => {
+                options.export_svg = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err("--export-svg needs a file to write".into()),
+                }
+            }
+            "--solution" => options.solution = true,
+            "--no-gameover" => options.no_gameover = true,
+            "--selftest" => options.selftest = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ if options.filename.is_none() => options.filename = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    // Printing or exporting works on a single puzzle, not a directory or zip
+    // archive of them.
+
+    let zip = match options.filename {
+        Some(ref f) => f.to_lowercase().ends_with(".zip"),
+        None => false,
+    };
+
+    if (options.dir.is_some() || zip)
+        && (options.info || options.export_text || options.export_svg.is_some())
+    {
+        return Err("--info and --export-* need a single puzzle file".into());
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(a: &[&str]) -> Result<Options, String> {
+        parse(a.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn flags() {
+        let o = args(&["--no-gameover", "file.puz"]).unwrap();
+
+        assert_eq!(o.filename, Some("file.puz".into()));
+        assert!(o.no_gameover);
+        assert!(!o.selftest);
+        assert!(!o.mono);
+        assert!(!o.compact);
+        assert!(!o.center);
+        assert!(!o.overview);
+        assert_eq!(o.autosave_secs, 30);
+
+        let o = args(&[
+            "--autosave-secs",
+            "0",
+            "--start-solving",
+            "--compact",
+            "file.puz",
+        ])
+        .unwrap();
+
+        assert_eq!(o.autosave_secs, 0);
+        assert!(o.start_solving);
+        assert!(o.compact);
+    }
+
+    #[test]
+    fn format() {
+        let o = args(&["--format", "nyt", "file.json"]).unwrap();
+
+        assert_eq!(o.format, Some(Format::Nyt));
+        assert_eq!(o.key, None);
+        assert_eq!(o.filename, Some("file.json".into()));
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(args(&["file.puz"]).unwrap().layout, Layout::Qwerty);
+
+        let o = args(&["--layout", "colemak", "file.puz"]).unwrap();
+
+        assert_eq!(o.layout, Layout::Colemak);
+        assert!(args(&["--layout", "azerty", "file.puz"]).is_err());
+        assert!(args(&["--layout"]).is_err());
+    }
+
+    #[test]
+    fn dir() {
+        let o = args(&["--dir", "puzzles"]).unwrap();
+
+        assert_eq!(o.dir, Some("puzzles".into()));
+        assert_eq!(o.filename, None);
+        assert!(args(&["--dir"]).is_err());
+    }
+
+    #[test]
+    fn export_svg() {
+        let o = args(&["--export-svg", "out.svg", "--solution", "file.puz"]).unwrap();
+
+        assert_eq!(o.export_svg, Some("out.svg".into()));
+        assert!(o.solution);
+        assert_eq!(o.filename, Some("file.puz".into()));
+    }
+
+    #[test]
+    fn bad_args() {
+        assert!(args(&["--bogus", "file.puz"]).is_err());
+        assert!(args(&["--format", "ipuz", "file.puz"]).is_err());
+        assert!(args(&["--autosave-secs", "soon", "file.puz"]).is_err());
+        assert!(args(&["--key", "12345", "file.puz"]).is_err());
+        assert!(args(&["file.puz", "--theme"]).is_err());
+        assert!(args(&["one.puz", "two.puz"]).is_err());
+        assert!(args(&["--info", "--dir", "puzzles"]).is_err());
+        assert!(args(&["--export-text", "pack.ZIP"]).is_err());
+        assert!(args(&["--export-svg", "out.svg", "pack.zip"]).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+
+/// The .puz files in a directory, sorted by name. Subdirectories aren't
+/// searched.
+pub fn read_dir(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut puzzles = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+
+        let is_puz = path.to_string_lossy().to_lowercase().ends_with(".puz");
+
+        if is_puz && path.is_file() {
+            puzzles.push(path);
+        }
+    }
+
+    puzzles.sort();
+
+    Ok(puzzles)
+}
+
+/// Read every .puz file out of a zip archive, sorted by name.
+///
+/// Anything that isn't a .puz file is ignored.
+pub fn read_zip(path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut puzzles = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        if entry.is_dir() || !entry.name().to_lowercase().ends_with(".puz") {
+            continue;
+        }
+
+        let mut v = Vec::new();
+        entry.read_to_end(&mut v)?;
+
+        puzzles.push((entry.name().to_string(), v));
+    }
+
+    puzzles.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(puzzles)
+}
@@ -1,13 +1,17 @@
 extern crate nom;
 
+use nom::error::ErrorKind;
 use nom::number::complete::{le_u16, le_u8};
+use nom::{Err, IResult, Needed};
 
+use std::fmt;
+use std::ops::Range;
 use std::str;
 
-use encoding::all::ISO_8859_1;
-use encoding::{DecoderTrap, Encoding};
+use encoding::all::WINDOWS_1252;
+use encoding::{DecoderTrap, EncoderTrap, Encoding};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct PuzFile {
     pub preamble: Vec<u8>,
     pub checksum: u16,
@@ -33,23 +37,68 @@ pub struct PuzFile {
     pub copyright: String,
     pub clues: Vec<String>,
     pub notes: String,
+    /// Answers longer than one letter, one entry per cell, from the GRBS and
+    /// RTBL sections.
+    pub rebus: Vec<Option<String>>,
+    /// A byte of `MARKUP_*` flags per cell, from the GEXT section.
+    pub markup: Vec<u8>,
+    /// Seconds spent solving so far, from the LTIM section.
+    pub timer_elapsed: Option<u64>,
+    /// Whether the timer was still running when the puzzle was saved, from
+    /// the LTIM section.
+    pub timer_running: bool,
+    /// A version 2.0 file whose strings weren't all valid UTF-8, so they were
+    /// read as Windows-1252 and are written back that way.
+    pub cp1252_text: bool,
+    /// Where the extra sections are in the file this was parsed from.
+    pub extras_span: Range<usize>,
+    /// How many of the clues counted in the header weren't in the file. Their
+    /// places in `clues` are left empty.
+    pub missing_clues: usize,
 }
 
-named!(null_string_ascii<&[u8], String>,
-   do_parse!(
-       s: take_until!("\0") >>
-       take!(1) >>
-       ( ISO_8859_1.decode(s, DecoderTrap::Ignore).unwrap() )
-   )
-);
+/// The cell is circled.
+pub const MARKUP_CIRCLED: u8 = 0x80;
+/// The answer was given to the solver.
+pub const MARKUP_GIVEN: u8 = 0x40;
+/// The cell was marked wrong at some point.
+pub const MARKUP_PREVIOUSLY_INCORRECT: u8 = 0x20;
+/// The cell is marked wrong.
+pub const MARKUP_INCORRECT: u8 = 0x10;
+
+/// Decode a string from the file. Across Lite writes Windows-1252. The five
+/// bytes Windows-1252 leaves undefined come through as the matching control
+/// characters, so every byte decodes and this never fails.
+pub fn decode_string(bytes: &[u8]) -> String {
+    WINDOWS_1252
+        .decode(bytes, DecoderTrap::Strict)
+        .unwrap_or_default()
+}
+
+/// Encode a string for the file, the reverse of `decode_string`.
+pub fn encode_string(s: &str) -> Vec<u8> {
+    WINDOWS_1252
+        .encode(s, EncoderTrap::Strict)
+        .unwrap_or_else(|_| s.chars().map(|c| c as u8).collect())
+}
+
+/// Whether strings in a file with this version are UTF-8 rather than
+/// Windows-1252.
+fn is_utf8(version: &str) -> bool {
+    version.as_bytes() >= &b"2.0"[..]
+}
+
+named!(null_terminated, terminated!(take_until!("\0"), take!(1)));
+
+named!(null_string<&[u8], String>, map!(null_terminated, decode_string));
 
 named!(checksum, terminated!(take!(2), peek!(tag!("ACROSS&DOWN"))));
 
-named!(pub parse_all<&[u8], PuzFile>,
+named!(header<&[u8], PuzFile>,
     do_parse!(
         preamble: opt!(many_till!(take!(1), peek!(checksum))) >>
         checksum: flat_map!(checksum, le_u16) >>
-        magic: null_string_ascii >>
+        magic: null_string >>
         cib_checksum: le_u16 >>
         masked_low_checksum_1: le_u16 >>
         masked_low_checksum_2: le_u16 >>
@@ -64,13 +113,6 @@ named!(pub parse_all<&[u8], PuzFile>,
         num_clues: le_u16 >>
         unknown_bitmask: le_u16 >>
         scrambled: le_u16 >>
-        puzzle: map_res!(take!(width as usize * height as usize), str::from_utf8) >>
-        state: map_res!(take!(width as usize * height as usize), str::from_utf8) >>
-        title: null_string_ascii >>
-        author: null_string_ascii >>
-        copyright: null_string_ascii >>
-        clues: many_m_n!(num_clues as usize, num_clues as usize, null_string_ascii) >>
-        notes: null_string_ascii >>
         (PuzFile {
             preamble: match preamble {
                 Some(p) => p.0.iter().map(|x| x[0]).collect(),
@@ -92,22 +134,915 @@ named!(pub parse_all<&[u8], PuzFile>,
             num_clues,
             unknown_bitmask,
             scrambled,
-            puzzle: puzzle.into(),
-            state: state.into(),
-            title,
-            author,
-            copyright,
-            clues,
-            notes
+            puzzle: String::new(),
+            state: String::new(),
+            title: String::new(),
+            author: String::new(),
+            copyright: String::new(),
+            clues: Vec::new(),
+            notes: String::new(),
+            rebus: Vec::new(),
+            markup: Vec::new(),
+            timer_elapsed: None,
+            timer_running: false,
+            cp1252_text: false,
+            extras_span: 0..0,
+            missing_clues: 0,
         })
     )
 );
 
+named_args!(grid(size: usize)<&[u8], String>,
+    map!(map_res!(take!(size), str::from_utf8), String::from)
+);
+
+/// How many bytes a UTF-8 character takes, going by its first byte.
+fn utf8_width(first: u8) -> Option<usize> {
+    match first {
+        0x00..=0x7F => Some(1),
+        0xC0..=0xDF => Some(2),
+        0xE0..=0xEF => Some(3),
+        0xF0..=0xF7 => Some(4),
+        _ => None,
+    }
+}
+
+/// A grid of `size` cells in a version 2.0 file, where each cell is a UTF-8
+/// character and can take more than one byte.
+fn utf8_grid(input: &[u8], size: usize) -> IResult<&[u8], String> {
+    let mut len = 0;
+
+    for _ in 0..size {
+        let width = match input.get(len) {
+            Some(&b) => utf8_width(b).ok_or(Err::Error((&input[len..], ErrorKind::MapRes)))?,
+            None => return Err(Err::Incomplete(Needed::Size(1))),
+        };
+
+        if len + width > input.len() {
+            return Err(Err::Incomplete(Needed::Size(len + width - input.len())));
+        }
+
+        len += width;
+    }
+
+    match str::from_utf8(&input[..len]) {
+        Ok(s) => Ok((&input[len..], s.to_string())),
+        Err(_) => Err(Err::Error((input, ErrorKind::MapRes))),
+    }
+}
+
+/// An extra section after the notes: title, checksum and data.
+type Extra<'a> = (&'a [u8], u16, &'a [u8]);
+
+named!(extra<&[u8], Extra<'_>>,
+    do_parse!(
+        title: take!(4) >>
+        length: le_u16 >>
+        checksum: le_u16 >>
+        data: take!(length) >>
+        tag!("\0") >>
+        (title, checksum, data)
+    )
+);
+
+named!(extras<&[u8], Vec<Extra<'_>>>, many0!(complete!(extra)));
+
+fn find_extra<'a>(extras: &[Extra<'a>], title: &[u8]) -> Option<&'a [u8]> {
+    extras.iter().find(|e| e.0 == title).map(|e| e.2)
+}
+
+/// The RTBL section is a list of `key:answer;` entries, with keys padded to
+/// two characters with a leading space. It's decoded with `text`, the same
+/// way as the file's other strings.
+fn rebus_table<F: Fn(&[u8]) -> String>(data: &[u8], text: F) -> Vec<(u8, String)> {
+    text(data)
+        .split(';')
+        .filter_map(|entry| {
+            let mut parts = entry.splitn(2, ':');
+            let key = parts.next()?.trim().parse().ok()?;
+            let answer = parts.next()?;
+
+            Some((key, answer.to_string()))
+        })
+        .collect()
+}
+
+/// GRBS has a byte per cell which is zero for ordinary cells, or one more than
+/// the cell's key in the RTBL table.
+fn rebus<F: Fn(&[u8]) -> String>(extras: &[Extra], size: usize, text: F) -> Vec<Option<String>> {
+    let (grid, table) = match (find_extra(extras, b"GRBS"), find_extra(extras, b"RTBL")) {
+        (Some(grid), Some(table)) if grid.len() == size => (grid, rebus_table(table, text)),
+        _ => return vec![None; size],
+    };
+
+    grid.iter()
+        .map(|&b| {
+            if b == 0 {
+                return None;
+            }
+
+            table.iter().find(|e| e.0 == b - 1).map(|e| e.1.clone())
+        })
+        .collect()
+}
+
+/// GEXT has a byte of flags per cell.
+fn markup(extras: &[Extra], size: usize) -> Vec<u8> {
+    match find_extra(extras, b"GEXT") {
+        Some(markup) if markup.len() == size => markup.to_vec(),
+        _ => vec![0; size],
+    }
+}
+
+/// LTIM holds the elapsed seconds and whether the timer is stopped, e.g.
+/// `123,1`. A timer that doesn't say is taken to be stopped.
+fn timer(extras: &[Extra]) -> Option<(u64, bool)> {
+    let data = find_extra(extras, b"LTIM")?;
+    let mut fields = str::from_utf8(data).ok()?.split(',');
+
+    let elapsed = fields.next()?.trim().parse().ok()?;
+    let running = fields.next().map(str::trim) == Some("0");
+
+    Some((elapsed, running))
+}
+
+/// The LTIM section for a timer. The second field is 1 if the timer is
+/// stopped.
+fn encode_timer(elapsed: u64, running: bool) -> Vec<u8> {
+    let stopped = if running { 0 } else { 1 };
+
+    encode_extra(b"LTIM", format!("{},{}", elapsed, stopped).as_bytes())
+}
+
+/// An extra section as it's written in the file, with its checksum.
+fn encode_extra(title: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut v = title.to_vec();
+    v.extend(&(data.len() as u16).to_le_bytes());
+    v.extend(&checksum_region(data, 0).to_le_bytes());
+    v.extend(data);
+    v.push(0);
+    v
+}
+
+/// The part of the file that was being read when parsing failed.
+#[derive(Debug, PartialEq)]
+pub enum Section {
+    Header,
+    Board,
+    Title,
+    Author,
+    Copyright,
+    Notes,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Section::Header => write!(f, "header"),
+            Section::Board => write!(f, "board"),
+            Section::Title => write!(f, "title"),
+            Section::Author => write!(f, "author"),
+            Section::Copyright => write!(f, "copyright"),
+            Section::Notes => write!(f, "notes"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub section: Section,
+    /// Approximate byte offset into the file where parsing failed.
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "failed parsing {} at byte {:#X}: {}",
+            self.section, self.offset, self.reason
+        )
+    }
+}
+
+type SectionResult<'a, T> = Result<(&'a [u8], T), (Section, &'a [u8], Err<(&'a [u8], ErrorKind)>)>;
+
+/// Run one parser, remembering which section it was reading and where that
+/// section started if it fails.
+fn section<'a, T, F>(section: Section, i: &'a [u8], f: F) -> SectionResult<'a, T>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+{
+    f(i).map_err(|e| (section, i, e))
+}
+
+fn parse_sections<'a>(input: &'a [u8]) -> SectionResult<'a, PuzFile> {
+    let (i, mut p) = section(Section::Header, input, header)?;
+
+    let size = p.width as usize * p.height as usize;
+
+    // Strings and grids are UTF-8 from version 2.0 on.
+
+    let utf8 = is_utf8(&p.version);
+    let board = |i| {
+        if utf8 {
+            utf8_grid(i, size)
+        } else {
+            grid(i, size)
+        }
+    };
+
+    let (i, puzzle) = section(Section::Board, i, board)?;
+    let (i, state) = section(Section::Board, i, board)?;
+
+    let (i, title) = section(Section::Title, i, null_terminated)?;
+    let (i, author) = section(Section::Author, i, null_terminated)?;
+    let (mut i, copyright) = section(Section::Copyright, i, null_terminated)?;
+
+    // A file can end before all its clues do. Stop where the data runs out,
+    // so that what's there can still be played. Read no more than the grid
+    // has words for; a header that counts more than that is refused by
+    // `verify_clues`, since there's no telling where the clues end.
+
+    let wanted = (p.num_clues as usize).min(clue_count(&puzzle, p.width));
+    let mut clues = Vec::new();
+
+    while clues.len() < wanted {
+        match null_terminated(i) {
+            Ok((rest, clue)) => {
+                clues.push(clue);
+                i = rest;
+            }
+            Err(_) => break,
+        }
+    }
+
+    let (i, notes) = if clues.len() < wanted {
+        (i, &b""[..])
+    } else {
+        section(Section::Notes, i, null_terminated)?
+    };
+
+    p.missing_clues = p.num_clues as usize - clues.len();
+
+    // Extra sections are optional, and anything after the last one that
+    // makes sense is ignored.
+
+    let extras_start = input.len() - i.len();
+    let (i, extras) = extras(i).unwrap_or((i, Vec::new()));
+
+    // If any string in a version 2.0 file isn't UTF-8, it was most likely
+    // written by something that didn't know about the change, so all of them
+    // are read as Windows-1252. That way they're written back unchanged.
+
+    let strings = [title, author, copyright, notes];
+    let cp1252 = utf8
+        && !strings
+            .iter()
+            .chain(&clues)
+            .chain(&find_extra(&extras, b"RTBL"))
+            .all(|s| str::from_utf8(s).is_ok());
+    let text = |s: &[u8]| match str::from_utf8(s) {
+        Ok(s) if utf8 && !cp1252 => s.to_string(),
+        _ => decode_string(s),
+    };
+
+    let mut clues: Vec<String> = clues.into_iter().map(text).collect();
+    clues.resize(wanted, String::new());
+
+    p.puzzle = puzzle;
+    p.state = state;
+    p.title = text(title);
+    p.author = text(author);
+    p.copyright = text(copyright);
+    p.clues = clues;
+    p.notes = text(notes);
+    p.cp1252_text = cp1252;
+    p.rebus = rebus(&extras, size, text);
+    p.markup = markup(&extras, size);
+    if let Some((elapsed, running)) = timer(&extras) {
+        p.timer_elapsed = Some(elapsed);
+        p.timer_running = running;
+    }
+    p.extras_span = extras_start..input.len() - i.len();
+
+    Ok((i, p))
+}
+
+/// Parse a whole file as a nom parser, for combining with other parsers.
+/// Whatever comes after the last extra section is left over.
+pub fn parse_all(input: &[u8]) -> IResult<&[u8], PuzFile> {
+    parse_sections(input).map_err(|(_, _, e)| e)
+}
+
+/// Parse a whole file, describing where things went wrong if they did.
+pub fn parse(input: &[u8]) -> Result<PuzFile, ParseError> {
+    parse_sections(input)
+        .map(|(_, p)| p)
+        .map_err(|(section, start, e)| {
+            let (rest, reason) = match e {
+                Err::Error((rest, kind)) | Err::Failure((rest, kind)) => {
+                    (rest, kind.description().to_lowercase())
+                }
+                Err::Incomplete(_) => (start, "unexpected end of data".to_string()),
+            };
+
+            ParseError {
+                section,
+                offset: input.len() - rest.len(),
+                reason,
+            }
+        })
+}
+
+/// The header from the file checksum up to the board.
+const HEADER_LEN: usize = 0x34;
+
+/// The checksums stored in the header.
+#[derive(Debug, PartialEq)]
+pub struct Checksums {
+    pub file: u16,
+    pub cib: u16,
+    pub masked_low: [u8; 4],
+    pub masked_high: [u8; 4],
+}
+
+/// A clue list that's the wrong length for the grid.
+#[derive(Debug, PartialEq)]
+pub struct ClueCountError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for ClueCountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the grid has {} clues but the file has {}",
+            self.expected, self.found
+        )
+    }
+}
+
+/// A checksum in the header that doesn't match the rest of the file.
+#[derive(Debug, PartialEq)]
+pub struct ChecksumError {
+    pub name: &'static str,
+    pub expected: u16,
+    pub found: u16,
+}
+
+impl fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} checksum is {:#06X}, expected {:#06X}",
+            self.name, self.found, self.expected
+        )
+    }
+}
+
+/// Across Lite's checksum: rotate right one bit, then add the next byte.
+fn checksum_region(data: &[u8], checksum: u16) -> u16 {
+    data.iter().fold(checksum, |c, &b| {
+        let c = if c & 1 == 1 {
+            (c >> 1) + 0x8000
+        } else {
+            c >> 1
+        };
+
+        c.wrapping_add(u16::from(b))
+    })
+}
+
+/// Whether `c` marks a black square in the solution. Besides `.`, some files
+/// use `:` or a blank as a placeholder; anything else, symbols included, is
+/// taken as an answer.
+pub fn is_black(c: char) -> bool {
+    c == '.' || c == ':' || c.is_whitespace() || c.is_control()
+}
+
+impl PuzFile {
+    /// The width, height, clue count, bitmask and scrambled flag, which are
+    /// covered by the CIB checksum.
+    fn cib(&self) -> Vec<u8> {
+        let mut v = vec![self.width, self.height];
+
+        for n in &[self.num_clues, self.unknown_bitmask, self.scrambled] {
+            v.extend(&n.to_le_bytes());
+        }
+
+        v
+    }
+
+    /// Encode a string the way this version of the format stores it.
+    pub fn encode_text(&self, s: &str) -> Vec<u8> {
+        if is_utf8(&self.version) && !self.cp1252_text {
+            s.as_bytes().to_vec()
+        } else {
+            encode_string(s)
+        }
+    }
+
+    /// Continue a checksum over the title, author, copyright, clues and notes.
+    /// Empty strings are skipped, and the notes only count from version 1.3.
+    fn text_checksum(&self, mut checksum: u16) -> u16 {
+        for s in &[&self.title, &self.author, &self.copyright] {
+            if !s.is_empty() {
+                checksum = checksum_region(&self.encode_text(s), checksum);
+                checksum = checksum_region(b"\0", checksum);
+            }
+        }
+
+        for clue in &self.clues {
+            checksum = checksum_region(&self.encode_text(clue), checksum);
+        }
+
+        if !self.notes.is_empty() && self.version.as_bytes() >= &b"1.3"[..] {
+            checksum = checksum_region(&self.encode_text(&self.notes), checksum);
+            checksum = checksum_region(b"\0", checksum);
+        }
+
+        checksum
+    }
+
+    /// A checksum of the solution alone, which stays the same as the puzzle is
+    /// solved.
+    pub fn solution_checksum(&self) -> u16 {
+        checksum_region(self.puzzle.as_bytes(), 0)
+    }
+
+    /// Work out what the checksums in the header should be.
+    pub fn checksums(&self) -> Checksums {
+        let cib = checksum_region(&self.cib(), 0);
+
+        let mut file = checksum_region(self.puzzle.as_bytes(), cib);
+        file = checksum_region(self.state.as_bytes(), file);
+        file = self.text_checksum(file);
+
+        let parts = [
+            cib,
+            checksum_region(self.puzzle.as_bytes(), 0),
+            checksum_region(self.state.as_bytes(), 0),
+            self.text_checksum(0),
+        ];
+
+        let mut masked_low = [0; 4];
+        let mut masked_high = [0; 4];
+
+        for (i, part) in parts.iter().enumerate() {
+            masked_low[i] = b"ICHE"[i] ^ (part & 0xFF) as u8;
+            masked_high[i] = b"ATED"[i] ^ (part >> 8) as u8;
+        }
+
+        Checksums {
+            file,
+            cib,
+            masked_low,
+            masked_high,
+        }
+    }
+
+    /// Characters other than `.` in the solution that are taken as black
+    /// squares, with how many squares hold each, in the order they first
+    /// appear.
+    pub fn unexpected_cells(&self) -> Vec<(char, usize)> {
+        let mut found: Vec<(char, usize)> = Vec::new();
+
+        for c in self.puzzle.chars().filter(|&c| c != '.' && is_black(c)) {
+            match found.iter_mut().find(|f| f.0 == c) {
+                Some(f) => f.1 += 1,
+                None => found.push((c, 1)),
+            }
+        }
+
+        found
+    }
+
+    /// The number in the corner of each cell, for cells that have one.
+    pub fn cell_numbers(&self) -> Vec<Option<u16>> {
+        let mut numbers = vec![None; self.puzzle.chars().count()];
+
+        for (i, number, _, _) in clue_starts(&self.puzzle, self.width) {
+            numbers[i] = Some(number);
+        }
+
+        numbers
+    }
+
+    /// Check there's a clue for every word in the grid, and that the header
+    /// counts the same number.
+    pub fn verify_clues(&self) -> Result<(), ClueCountError> {
+        let expected = clue_count(&self.puzzle, self.width);
+
+        for &found in &[self.clues.len(), self.num_clues as usize] {
+            if found != expected {
+                return Err(ClueCountError { expected, found });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the checksums stored in the header against the puzzle.
+    pub fn verify_checksums(&self) -> Result<(), ChecksumError> {
+        let c = self.checksums();
+
+        let checks = [
+            ("CIB", c.cib, self.cib_checksum),
+            ("file", c.file, self.checksum),
+            (
+                "masked low",
+                u16::from_le_bytes([c.masked_low[0], c.masked_low[1]]),
+                self.masked_low_checksum_1,
+            ),
+            (
+                "masked low",
+                u16::from_le_bytes([c.masked_low[2], c.masked_low[3]]),
+                self.masked_low_checksum_2,
+            ),
+            (
+                "masked high",
+                u16::from_le_bytes([c.masked_high[0], c.masked_high[1]]),
+                self.masked_high_checksum_1,
+            ),
+            (
+                "masked high",
+                u16::from_le_bytes([c.masked_high[2], c.masked_high[3]]),
+                self.masked_high_checksum_2,
+            ),
+        ];
+
+        for &(name, expected, found) in &checks {
+            if expected != found {
+                return Err(ChecksumError {
+                    name,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Locked puzzles have their solution scrambled with a four digit key.
+    pub fn is_scrambled(&self) -> bool {
+        self.scrambled & 0x0004 != 0
+    }
+
+    /// Restore the solution of a locked puzzle. The key is checked against
+    /// `scrambled_checksum`, and the puzzle is left alone if it's wrong.
+    pub fn unscramble(&mut self, key: u16) -> Result<(), ChecksumError> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+
+        let digits: Vec<u8> = format!("{:04}", key).bytes().map(|b| b - b'0').collect();
+
+        // Letters are scrambled in column order, skipping black cells.
+
+        let columns = transpose(self.puzzle.as_bytes(), width, height);
+        let mut letters: Vec<u8> = columns.iter().cloned().filter(|&c| c != b'.').collect();
+
+        if digits.len() != 4 || letters.iter().any(|c| !c.is_ascii_uppercase()) {
+            return Err(ChecksumError {
+                name: "scrambled",
+                expected: self.scrambled_checksum,
+                found: 0,
+            });
+        }
+
+        for &k in digits.iter().rev() {
+            letters = uninterleave(&letters);
+
+            let len = letters.len();
+            letters.rotate_right(k as usize % len.max(1));
+
+            for (i, c) in letters.iter_mut().enumerate() {
+                let shift = digits[i % 4];
+                *c = b'A' + (*c - b'A' + 26 - shift) % 26;
+            }
+        }
+
+        let found = checksum_region(&letters, 0);
+
+        if found != self.scrambled_checksum {
+            return Err(ChecksumError {
+                name: "scrambled",
+                expected: self.scrambled_checksum,
+                found,
+            });
+        }
+
+        let mut letters = letters.into_iter();
+        let columns: Vec<u8> = columns
+            .iter()
+            .map(|&c| {
+                if c == b'.' {
+                    c
+                } else {
+                    letters.next().unwrap()
+                }
+            })
+            .collect();
+
+        self.puzzle = String::from_utf8(transpose(&columns, height, width)).unwrap();
+        self.scrambled = 0;
+        self.scrambled_checksum = 0;
+
+        Ok(())
+    }
+
+    /// Write `state` into the file this was parsed from, updating the
+    /// checksums to match. In a version 2.0 file the new state can take a
+    /// different number of bytes to the old one, so the extra sections are
+    /// moved along to make room. Older files only have room for ASCII, so
+    /// anything else is left empty.
+    pub fn write_state(&mut self, data: &mut Vec<u8>) {
+        if !is_utf8(&self.version) {
+            self.state = self
+                .state
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '-' })
+                .collect();
+        }
+
+        let start = self.preamble.len();
+        let size = self.width as usize * self.height as usize;
+        let state = start + HEADER_LEN + self.puzzle.len();
+
+        let old_len = match utf8_grid(&data[state..], size) {
+            Ok((rest, _)) if is_utf8(&self.version) => data.len() - state - rest.len(),
+            _ => size,
+        };
+        let new_len = self.state.len();
+
+        data.splice(state..state + old_len, self.state.bytes());
+
+        self.extras_span =
+            self.extras_span.start + new_len - old_len..self.extras_span.end + new_len - old_len;
+
+        let c = self.checksums();
+
+        data[start..start + 2].copy_from_slice(&c.file.to_le_bytes());
+        data[start + 0x0E..start + 0x10].copy_from_slice(&c.cib.to_le_bytes());
+        data[start + 0x10..start + 0x14].copy_from_slice(&c.masked_low);
+        data[start + 0x14..start + 0x18].copy_from_slice(&c.masked_high);
+    }
+
+    /// Write an LTIM section with the given elapsed time, and whether the
+    /// timer is running, into the file this was parsed from, replacing the old
+    /// one if there was one.
+    pub fn write_timer(&self, data: &mut Vec<u8>, elapsed: u64, running: bool) {
+        let span = self.extras_span.clone();
+
+        let timer = encode_timer(elapsed, running);
+        let old = extras(&data[span.clone()])
+            .map(|(_, e)| e)
+            .unwrap_or_default();
+
+        let mut sections: Vec<u8> = old
+            .iter()
+            .flat_map(|e| {
+                if e.0 == b"LTIM" {
+                    timer.clone()
+                } else {
+                    encode_extra(e.0, e.2)
+                }
+            })
+            .collect();
+
+        if find_extra(&old, b"LTIM").is_none() {
+            sections.extend(timer);
+        }
+
+        data.splice(span, sections);
+    }
+
+    /// The whole file, with its checksums worked out afresh. The rebus,
+    /// markup and timer are written as extra sections if there are any; other
+    /// extra sections the file was parsed from had are left out.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let c = self.checksums();
+        let mut v = self.preamble.clone();
+
+        v.extend(&c.file.to_le_bytes());
+        v.extend(encode_string(&self.magic));
+        v.push(0);
+        v.extend(&c.cib.to_le_bytes());
+        v.extend(&c.masked_low);
+        v.extend(&c.masked_high);
+
+        let mut version = self.version.as_bytes().to_vec();
+        version.resize(4, 0);
+        v.extend(version);
+
+        v.extend(&self.reserved_1.to_le_bytes());
+        v.extend(&self.scrambled_checksum.to_le_bytes());
+
+        let mut reserved_2 = self.reserved_2.clone();
+        reserved_2.resize(12, 0);
+        v.extend(reserved_2);
+
+        v.push(self.width);
+        v.push(self.height);
+        v.extend(&self.num_clues.to_le_bytes());
+        v.extend(&self.unknown_bitmask.to_le_bytes());
+        v.extend(&self.scrambled.to_le_bytes());
+
+        v.extend(self.puzzle.as_bytes());
+        v.extend(self.state.as_bytes());
+
+        let strings = [&self.title, &self.author, &self.copyright];
+
+        for s in strings
+            .iter()
+            .cloned()
+            .chain(&self.clues)
+            .chain(Some(&self.notes))
+        {
+            v.extend(self.encode_text(s));
+            v.push(0);
+        }
+
+        // Rebus answers go in a table, each under a key one less than the
+        // byte its cells have in the grid.
+
+        if self.rebus.iter().any(Option::is_some) {
+            let mut answers: Vec<&String> = Vec::new();
+            let mut grid = Vec::new();
+
+            for answer in &self.rebus {
+                grid.push(match *answer {
+                    Some(ref a) => match answers.iter().position(|&b| b == a) {
+                        Some(i) => i as u8 + 1,
+                        None => {
+                            answers.push(a);
+                            answers.len() as u8
+                        }
+                    },
+                    None => 0,
+                });
+            }
+
+            let table: String = answers
+                .iter()
+                .enumerate()
+                .map(|(i, a)| format!("{:>2}:{};", i, a))
+                .collect();
+
+            v.extend(encode_extra(b"GRBS", &grid));
+            v.extend(encode_extra(b"RTBL", &self.encode_text(&table)));
+        }
+
+        if let Some(elapsed) = self.timer_elapsed {
+            v.extend(encode_timer(elapsed, self.timer_running));
+        }
+
+        if self.markup.iter().any(|&m| m != 0) {
+            v.extend(encode_extra(b"GEXT", &self.markup));
+        }
+
+        v
+    }
+}
+
+/// Turn rows of `width` cells into rows of `height`, i.e. columns.
+fn transpose(cells: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut v = Vec::with_capacity(cells.len());
+
+    for x in 0..width {
+        for y in 0..height {
+            v.push(cells[y * width + x]);
+        }
+    }
+
+    v
+}
+
+/// Undo the shuffle that takes the second half of the letters and the first
+/// half in turns, starting with the second half.
+fn uninterleave(s: &[u8]) -> Vec<u8> {
+    let mid = s.len() / 2;
+    let mut v = s.to_vec();
+
+    for i in 0..mid {
+        v[mid + i] = s[2 * i];
+        v[i] = s[2 * i + 1];
+    }
+
+    v
+}
+
+/// Put separately numbered across and down clues into the single list `clues`
+/// uses: the order their cells are numbered in, across before down at the same
+/// cell.
+///
+/// Numbers are worked out from the grid, where `.` is a black cell. A numbered
+/// word with no clue gets an empty one so the rest still line up.
+pub fn clues_in_scan_order(
+    across: &[(u16, String)],
+    down: &[(u16, String)],
+    grid: &str,
+    width: u8,
+) -> Vec<String> {
+    let find = |clues: &[(u16, String)], number: u16| {
+        clues
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, clue)| clue.clone())
+            .unwrap_or_default()
+    };
+
+    let mut clues = Vec::new();
+
+    for (_, number, starts_across, starts_down) in clue_starts(grid, width) {
+        if starts_across {
+            clues.push(find(across, number));
+        }
+
+        if starts_down {
+            clues.push(find(down, number));
+        }
+    }
+
+    clues
+}
+
+/// How many clues a grid needs: one for each across and down word.
+fn clue_count(grid: &str, width: u8) -> usize {
+    clue_starts(grid, width)
+        .iter()
+        .map(|&(_, _, across, down)| across as usize + down as usize)
+        .sum()
+}
+
+/// The numbered cells of a grid, where [`is_black`] cells are black, in
+/// reading order: each cell's index and number, and whether across and down
+/// words start there.
+fn clue_starts(grid: &str, width: u8) -> Vec<(usize, u16, bool, bool)> {
+    let width = width as usize;
+    let cells: Vec<bool> = grid.chars().map(|c| !is_black(c)).collect();
+    let height = cells.len().checked_div(width).unwrap_or(0);
+
+    let white = |x: usize, y: usize| cells[y * width + x];
+
+    let mut starts = Vec::new();
+    let mut number = 1;
+
+    for y in 0..height {
+        for x in 0..width {
+            if !white(x, y) {
+                continue;
+            }
+
+            let across = (x == 0 || !white(x - 1, y)) && x + 1 < width && white(x + 1, y);
+            let down = (y == 0 || !white(x, y - 1)) && y + 1 < height && white(x, y + 1);
+
+            if across || down {
+                starts.push((y * width + x, number, across, down));
+                number += 1;
+            }
+        }
+    }
+
+    starts
+}
+
+impl PuzFile {
+    /// True if this was read from a .puz file rather than converted from some
+    /// other format.
+    pub fn is_puz(&self) -> bool {
+        self.magic == "ACROSS&DOWN"
+    }
+
+    /// Diagramless puzzles don't show the solver where the black cells are.
+    pub fn is_diagramless(&self) -> bool {
+        self.unknown_bitmask == 0x0401
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use nom::Err;
 
+    #[test]
+    fn scan_order() {
+        let across = vec![(1, "PUZ".to_string()), (3, "POO".to_string())];
+        let down = vec![(2, "ZOO".to_string()), (1, "POP".to_string())];
+
+        assert_eq!(
+            clues_in_scan_order(&across, &down, "PUZO.OPOO", 3),
+            vec!["PUZ", "POP", "ZOO", "POO"]
+        );
+        assert_eq!(
+            clues_in_scan_order(&across, &[], "PUZO.OPOO", 3),
+            vec!["PUZ", "", "", "POO"]
+        );
+    }
+
     #[test]
     fn it_works() {
         let d = include_bytes!("../assets/test1.puz");
@@ -164,6 +1099,364 @@ mod tests {
         assert_eq!(p.num_clues, 4);
     }
 
+    /// Build an extra section, with a dummy checksum.
+    fn extra_section(title: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut v = title.to_vec();
+        v.extend(&[data.len() as u8, (data.len() >> 8) as u8, 0, 0]);
+        v.extend(data);
+        v.push(0);
+        v
+    }
+
+    #[test]
+    fn rebus() {
+        let p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        assert_eq!(p.rebus, vec![None; 9]);
+
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+        d.extend(extra_section(b"GRBS", &[0, 0, 3, 0, 0, 0, 1, 0, 0]));
+        d.extend(extra_section(b"RTBL", b" 0:POP; 2:ZIP;"));
+
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.rebus[0], None);
+        assert_eq!(p.rebus[2], Some("ZIP".to_string()));
+        assert_eq!(p.rebus[6], Some("POP".to_string()));
+        assert_eq!(p.puzzle, concat!("PUZ", "O.O", "POO"));
+
+        // Version 2.0 answers are UTF-8 like the rest of the text.
+
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+        d[0x18..0x1c].copy_from_slice(b"2.0\0");
+        d.extend(extra_section(b"GRBS", &[0, 0, 1, 0, 0, 0, 0, 0, 0]));
+        d.extend(extra_section(b"RTBL", b" 0:CAF\xc3\x89;"));
+
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.rebus[2], Some("CAF\u{c9}".to_string()));
+        assert_eq!(parse(&p.to_bytes()).unwrap().rebus, p.rebus);
+    }
+
+    #[test]
+    fn markup() {
+        let p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        assert_eq!(p.markup, vec![0; 9]);
+
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+        d.extend(extra_section(b"GEXT", &[0x80, 0, 0, 0, 0, 0, 0, 0x10, 0]));
+
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.markup[0], MARKUP_CIRCLED);
+        assert_eq!(p.markup[7], MARKUP_INCORRECT);
+
+        // Too short for the grid.
+
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+        d.extend(extra_section(b"GEXT", &[0x80]));
+
+        assert_eq!(parse(&d).unwrap().markup, vec![0; 9]);
+    }
+
+    #[test]
+    fn checksums() {
+        for d in &[
+            &include_bytes!("../assets/test1.puz")[..],
+            &include_bytes!("../assets/test2.puz")[..],
+        ] {
+            let p = parse(d).unwrap();
+            let c = p.checksums();
+
+            assert_eq!(c.file, p.checksum);
+            assert_eq!(c.cib, p.cib_checksum);
+            assert_eq!(
+                c.masked_low,
+                [
+                    p.masked_low_checksum_1 as u8,
+                    (p.masked_low_checksum_1 >> 8) as u8,
+                    p.masked_low_checksum_2 as u8,
+                    (p.masked_low_checksum_2 >> 8) as u8,
+                ]
+            );
+            assert_eq!(
+                c.masked_high,
+                [
+                    p.masked_high_checksum_1 as u8,
+                    (p.masked_high_checksum_1 >> 8) as u8,
+                    p.masked_high_checksum_2 as u8,
+                    (p.masked_high_checksum_2 >> 8) as u8,
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn to_bytes() {
+        for d in &[
+            &include_bytes!("../assets/test1.puz")[..],
+            &include_bytes!("../assets/test2.puz")[..],
+            &include_bytes!("../assets/test4.puz")[..],
+            &include_bytes!("../assets/utf8.puz")[..],
+        ] {
+            let p = parse(d).unwrap();
+
+            assert_eq!(&p.to_bytes()[..], *d);
+        }
+
+        // Anything after the last extra section isn't kept.
+
+        let d = include_bytes!("../assets/test3.puz");
+        let p = parse(d).unwrap();
+
+        assert_eq!(&p.to_bytes()[..], &d[..d.len() - 2]);
+
+        // Changes come back out with the checksums to match.
+
+        let mut p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        p.state = concat!("PU-", "-.-", "---").into();
+        p.title = "Another".into();
+        p.clues[1] = "Changed".into();
+        p.rebus[0] = Some("PUZ".into());
+        p.rebus[8] = Some("OO".into());
+        p.markup[4] = MARKUP_CIRCLED;
+        p.timer_elapsed = Some(75);
+        p.timer_running = true;
+
+        let q = parse(&p.to_bytes()).unwrap();
+
+        assert_eq!(q.verify_checksums(), Ok(()));
+        assert_eq!(q.puzzle, p.puzzle);
+        assert_eq!(q.state, p.state);
+        assert_eq!(q.title, "Another");
+        assert_eq!(q.author, p.author);
+        assert_eq!(q.clues, p.clues);
+        assert_eq!(q.notes, p.notes);
+        assert_eq!(q.rebus, p.rebus);
+        assert_eq!(q.markup, p.markup);
+        assert_eq!(q.timer_elapsed, Some(75));
+        assert!(q.timer_running);
+    }
+
+    #[test]
+    fn nom_parser() {
+        let d = include_bytes!("../assets/test2.puz");
+        let (rest, p) = parse_all(d).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(p.puzzle, "PUZO.OPOO");
+
+        let longer = [&d[..], b"more"].concat();
+        let (rest, _) = parse_all(&longer).unwrap();
+
+        assert_eq!(rest, b"more");
+        assert!(parse_all(&d[..0x40]).is_err());
+    }
+
+    #[test]
+    fn unexpected_cells() {
+        let p = parse(include_bytes!("../assets/test6.puz")).unwrap();
+
+        assert_eq!(p.unexpected_cells(), vec![(':', 1)]);
+        assert_eq!(p.verify_checksums(), Ok(()));
+
+        let p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        assert_eq!(p.unexpected_cells(), vec![]);
+
+        // A `:` where test2 has its black square splits the words around it
+        // the same way.
+        let split = parse(include_bytes!("../assets/test7.puz")).unwrap();
+
+        assert_eq!(split.unexpected_cells(), vec![(':', 1)]);
+        assert_eq!(split.cell_numbers(), p.cell_numbers());
+        assert_eq!(split.verify_clues(), Ok(()));
+    }
+
+    #[test]
+    fn verify_checksums() {
+        let mut p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        assert_eq!(p.verify_checksums(), Ok(()));
+
+        p.cib_checksum ^= 1;
+
+        assert_eq!(p.verify_checksums().unwrap_err().name, "CIB");
+
+        let mut p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+        p.clues[0].push('!');
+
+        assert_eq!(p.verify_checksums().unwrap_err().name, "file");
+    }
+
+    /// The forward direction of `unscramble`.
+    fn scramble(p: &mut PuzFile, key: u16) {
+        let width = p.width as usize;
+        let height = p.height as usize;
+        let digits: Vec<u8> = format!("{:04}", key).bytes().map(|b| b - b'0').collect();
+
+        let columns = transpose(p.puzzle.as_bytes(), width, height);
+        let mut letters: Vec<u8> = columns.iter().cloned().filter(|&c| c != b'.').collect();
+
+        p.scrambled_checksum = checksum_region(&letters, 0);
+
+        for &k in &digits {
+            for (i, c) in letters.iter_mut().enumerate() {
+                *c = b'A' + (*c - b'A' + digits[i % 4]) % 26;
+            }
+
+            letters.rotate_left(k as usize);
+
+            let mid = letters.len() / 2;
+            let mut v = Vec::new();
+            for i in 0..mid {
+                v.push(letters[mid + i]);
+                v.push(letters[i]);
+            }
+            if letters.len() % 2 == 1 {
+                v.push(letters[letters.len() - 1]);
+            }
+            letters = v;
+        }
+
+        let mut letters = letters.into_iter();
+        let columns: Vec<u8> = columns
+            .iter()
+            .map(|&c| {
+                if c == b'.' {
+                    c
+                } else {
+                    letters.next().unwrap()
+                }
+            })
+            .collect();
+
+        p.puzzle = String::from_utf8(transpose(&columns, height, width)).unwrap();
+        p.scrambled = 0x0004;
+    }
+
+    #[test]
+    fn unscramble() {
+        let original = parse(include_bytes!("../assets/test1.puz")).unwrap();
+
+        let mut p = original.clone();
+        scramble(&mut p, 1234);
+
+        assert!(p.is_scrambled());
+        assert_ne!(p.puzzle, original.puzzle);
+
+        assert_eq!(p.unscramble(4321).unwrap_err().name, "scrambled");
+        assert!(p.is_scrambled());
+
+        assert_eq!(p.unscramble(1234), Ok(()));
+        assert!(!p.is_scrambled());
+        assert_eq!(p.puzzle, original.puzzle);
+    }
+
+    #[test]
+    fn verify_clues() {
+        let mut p = parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        assert_eq!(p.verify_clues(), Ok(()));
+
+        p.clues.pop();
+
+        assert_eq!(
+            p.verify_clues(),
+            Err(ClueCountError {
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn write_state() {
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+
+        let mut p = parse(&d).unwrap();
+        p.state = concat!("PU-", "O.-", "--\u{c9}").into();
+        p.write_state(&mut d);
+
+        let q = parse(&d).unwrap();
+
+        assert_eq!(q.state, concat!("PU-", "O.-", "---"));
+        assert_eq!(q.puzzle, p.puzzle);
+        assert_eq!(q.checksum, q.checksums().file);
+    }
+
+    #[test]
+    fn utf8_board() {
+        let mut d = include_bytes!("../assets/utf8.puz").to_vec();
+        let mut p = parse(&d).unwrap();
+
+        assert_eq!(p.puzzle, concat!("\u{c9}T\u{c9}", "L.T", "UNE"));
+        assert_eq!(p.title, "\u{c9}t\u{e9}");
+        assert_eq!(p.clues[2], "Pas tout \u{e0} fait l'\u{e9}t\u{e9}");
+        assert_eq!(p.cell_numbers()[2], Some(2));
+        assert_eq!(p.verify_checksums(), Ok(()));
+
+        // Filling in the accented letters makes the state longer, which moves
+        // the extra sections along.
+
+        p.state = concat!("\u{c9}-\u{c9}", "-.-", "---").into();
+        p.write_state(&mut d);
+        p.write_timer(&mut d, 42, false);
+
+        let q = parse(&d).unwrap();
+
+        assert_eq!(q.state, p.state);
+        assert_eq!(q.timer_elapsed, Some(42));
+        assert_eq!(q.verify_checksums(), Ok(()));
+    }
+
+    #[test]
+    fn timer() {
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.timer_elapsed, Some(0));
+        assert!(!p.timer_running);
+
+        // Writing the same time back leaves the file as it was.
+
+        p.write_timer(&mut d, 0, false);
+
+        assert_eq!(&d[..], &include_bytes!("../assets/test2.puz")[..]);
+
+        // A file without a timer gets one after its other sections.
+
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+        d.truncate(p.extras_span.start);
+        d.extend(extra_section(b"GEXT", &[0x80, 0, 0, 0, 0, 0, 0, 0, 0]));
+
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.timer_elapsed, None);
+
+        p.write_timer(&mut d, 95, true);
+
+        let q = parse(&d).unwrap();
+
+        assert_eq!(q.timer_elapsed, Some(95));
+        assert!(q.timer_running);
+        assert_eq!(q.markup[0], MARKUP_CIRCLED);
+
+        // The old time is replaced rather than added to.
+
+        q.write_timer(&mut d, 3600, false);
+
+        let r = parse(&d).unwrap();
+
+        assert_eq!(r.timer_elapsed, Some(3600));
+        assert!(!r.timer_running);
+        assert_eq!(r.markup[0], MARKUP_CIRCLED);
+        assert_eq!(r.extras_span.end, d.len());
+    }
+
     #[test]
     fn leading_garbage() {
         let d = include_bytes!("../assets/test3.puz");
@@ -180,6 +1473,109 @@ mod tests {
         assert_eq!(p.num_clues, 4);
     }
 
+    #[test]
+    fn decode() {
+        assert_eq!(decode_string(b""), "");
+        assert_eq!(decode_string(b"caf\xe9"), "caf\u{e9}");
+        assert_eq!(decode_string(b"a\0b"), "a\u{0}b");
+
+        // Curly quotes live in the range where Windows-1252 and ISO-8859-1
+        // differ.
+        assert_eq!(decode_string(b"\x93hi\x94"), "\u{201c}hi\u{201d}");
+
+        // The bytes Windows-1252 leaves undefined come through as the
+        // matching control characters.
+        assert_eq!(
+            decode_string(b"\x81\x8d\x8f\x90\x9d\x93"),
+            "\u{81}\u{8d}\u{8f}\u{90}\u{9d}\u{201c}"
+        );
+    }
+
+    #[test]
+    fn utf8() {
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+
+        // Swap the title for one with an accent written in UTF-8.
+
+        let start = 0x34 + 2 * 9;
+        d.splice(start..start + 20, b"Caf\xc3\xa9".iter().cloned());
+
+        assert_eq!(parse(&d).unwrap().title, "Caf\u{c3}\u{a9}");
+
+        d[0x18..0x1c].copy_from_slice(b"2.0\0");
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.title, "Caf\u{e9}");
+        assert_eq!(p.encode_text(&p.title), b"Caf\xc3\xa9");
+
+        // If anything isn't valid UTF-8, it's all read as Windows-1252, and
+        // written back the way it was.
+
+        d.splice(start..start + 5, b"Caf\xe9\x93".iter().cloned());
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.title, "Caf\u{e9}\u{201c}");
+        assert!(p.cp1252_text);
+        assert_eq!(p.encode_text(&p.title), b"Caf\xe9\x93");
+        assert_eq!(p.encode_text("\u{c3}\u{a9}"), b"\xc3\xa9");
+
+        let fixed = p.to_bytes();
+
+        assert_eq!(fixed[HEADER_LEN..], d[HEADER_LEN..]);
+        assert_eq!(parse(&fixed).unwrap().verify_checksums(), Ok(()));
+    }
+
+    #[test]
+    fn truncated() {
+        let d = include_bytes!("../assets/test2.puz");
+
+        assert_eq!(
+            parse(&d[..80]).unwrap_err(),
+            ParseError {
+                section: Section::Title,
+                offset: 70,
+                reason: "unexpected end of data".into(),
+            }
+        );
+        assert_eq!(parse(&d[..60]).unwrap_err().section, Section::Board);
+
+        // Running out partway through the clues leaves the rest empty.
+
+        let p = parse(&d[..170]).unwrap();
+
+        assert_eq!(p.clues.len(), 4);
+        assert_eq!(
+            p.clues[0],
+            "The file extension for Across Lite puzzle files."
+        );
+        assert_eq!(p.clues[1], "");
+        assert_eq!(p.missing_clues, 3);
+        assert_eq!(p.verify_clues(), Ok(()));
+    }
+
+    #[test]
+    fn too_many_clues() {
+        let mut d = include_bytes!("../assets/test2.puz").to_vec();
+        d[0x2E] = 6;
+
+        let p = parse(&d).unwrap();
+
+        assert_eq!(p.clues.len(), 4);
+        assert_eq!(p.missing_clues, 2);
+        assert_eq!(p.timer_elapsed, Some(0));
+
+        // The notes could just as well be a fifth clue, so the file is
+        // refused rather than guessing.
+
+        assert_eq!(
+            p.verify_clues(),
+            Err(ClueCountError {
+                expected: 4,
+                found: 6
+            })
+        );
+    }
+
     #[test]
     fn rectangle() {
         let d = include_bytes!("../assets/test4.puz");
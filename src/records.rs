@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use config;
+use puzfile::PuzFile;
+
+/// Identifies a puzzle regardless of its file name: the CIB checksum from its
+/// header, along with its solution checksum, since puzzles of the same size
+/// and type share a CIB checksum.
+pub type Key = (u16, u16);
+
+pub fn key(p: &PuzFile) -> Key {
+    (p.cib_checksum, p.solution_checksum())
+}
+
+/// The puzzles that have been solved and the best time for each, kept in
+/// `completed` in the data directory. Each line holds the two checksums of a
+/// puzzle in hex and its best time in seconds.
+#[derive(Debug, Default, PartialEq)]
+pub struct Records {
+    best: HashMap<Key, u64>,
+}
+
+/// `$XDG_DATA_HOME/puzterm/completed`, falling back to
+/// `~/.local/share/puzterm/completed`.
+pub fn path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("completed"))
+}
+
+impl Records {
+    /// Load the records at `path`. A missing file means nothing has been
+    /// solved yet.
+    pub fn load(path: &Path) -> Records {
+        fs::read_to_string(path)
+            .map(|s| Records::parse(&s))
+            .unwrap_or_default()
+    }
+
+    /// Lines that can't be read are skipped rather than losing the rest.
+    pub fn parse(s: &str) -> Records {
+        let mut records = Records::default();
+
+        for line in s.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            if let [cib, solution, secs] = fields[..] {
+                if let (Ok(cib), Ok(solution), Ok(secs)) = (
+                    u16::from_str_radix(cib, 16),
+                    u16::from_str_radix(solution, 16),
+                    secs.parse(),
+                ) {
+                    records.record((cib, solution), secs);
+                }
+            }
+        }
+
+        records
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut keys: Vec<&Key> = self.best.keys().collect();
+        keys.sort();
+
+        let s: String = keys
+            .into_iter()
+            .map(|k| format!("{:04x} {:04x} {}\n", k.0, k.1, self.best[k]))
+            .collect();
+
+        fs::write(path, s)
+    }
+
+    /// The best time for a puzzle in seconds, if it has been solved.
+    pub fn best(&self, key: Key) -> Option<u64> {
+        self.best.get(&key).cloned()
+    }
+
+    /// Note that a puzzle was solved, keeping the time if it's the best so
+    /// far.
+    pub fn record(&mut self, key: Key, secs: u64) {
+        let best = self.best.entry(key).or_insert(secs);
+        *best = (*best).min(secs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn best_times() {
+        let mut r = Records::parse("00ab 1234 300\ngarbage\n00ab 4321 90\n");
+
+        assert_eq!(r.best((0xab, 0x1234)), Some(300));
+        assert_eq!(r.best((0xab, 0x4321)), Some(90));
+        assert_eq!(r.best((0xab, 0x1111)), None);
+
+        r.record((0xab, 0x1234), 200);
+        r.record((0xab, 0x4321), 120);
+
+        assert_eq!(r.best((0xab, 0x1234)), Some(200));
+        assert_eq!(r.best((0xab, 0x4321)), Some(90));
+
+        let path = env::temp_dir().join("puzterm-records/completed");
+        r.save(&path).unwrap();
+
+        assert_eq!(Records::load(&path), r);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use puzfile::{self, PuzFile};
+
+/// How `render_grid` draws a grid.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderOptions {
+    /// Draw each cell two columns wide and two rows tall, without clue
+    /// numbers, rather than four by three.
+    pub compact: bool,
+    /// Fill in the answers rather than the letters filled in so far.
+    pub solution: bool,
+    /// Leave the clue numbers out of the cells.
+    pub hide_numbers: bool,
+}
+
+/// Draw the grid with box-drawing characters, the way it looks while
+/// playing but without colors. Each row of text ends with a newline.
+pub fn render_grid(p: &PuzFile, options: &RenderOptions) -> String {
+    let width = p.width as usize;
+    let height = p.height as usize;
+    let (cell_width, cell_height) = if options.compact { (2, 2) } else { (4, 3) };
+    let inner = cell_width - 1;
+    let guess_row = cell_height - 2;
+
+    let puzzle: Vec<char> = p.puzzle.chars().collect();
+    let state: Vec<char> = p.state.chars().collect();
+    let numbers = p.cell_numbers();
+
+    let mut s = String::new();
+
+    for y in 0..height {
+        let mut lines = vec![String::new(); cell_height];
+
+        for x in 0..width {
+            let i = y * width + x;
+            let truth = puzzle.get(i).cloned().unwrap_or('.');
+            let black = puzfile::is_black(truth);
+
+            let letters = match (options.solution, p.rebus.get(i)) {
+                (true, Some(Some(rebus))) => rebus.clone(),
+                (true, _) => truth.to_string(),
+                (false, _) => match state.get(i) {
+                    Some(&c) if c != '-' && c != '.' => c.to_string(),
+                    _ => String::new(),
+                },
+            };
+
+            let number = match numbers.get(i) {
+                Some(&n) if !options.hide_numbers => n,
+                _ => None,
+            };
+            let circled = p.markup.get(i).cloned().unwrap_or(0) & puzfile::MARKUP_CIRCLED != 0;
+
+            for (row, line) in lines.iter_mut().enumerate() {
+                if row == cell_height - 1 {
+                    line.push_str(&"\u{2501}".repeat(inner));
+                    line.push_str(border_corner(x == width - 1, y == height - 1));
+                    continue;
+                }
+
+                let fill = if black {
+                    "\u{2588}".repeat(inner)
+                } else if row == 0 && guess_row > 0 {
+                    corner_text(number, circled)
+                } else if row == guess_row && !letters.is_empty() {
+                    guess_text(&letters, inner)
+                } else {
+                    " ".repeat(inner)
+                };
+
+                line.push_str(&fill);
+                line.push('\u{2503}');
+            }
+        }
+
+        for line in lines {
+            s.push_str(&line);
+            s.push('\n');
+        }
+    }
+
+    s
+}
+
+/// The corner at the bottom right of a cell, joining its borders to those of
+/// the cells beside and below it, if there are any.
+pub fn border_corner(last_column: bool, last_row: bool) -> &'static str {
+    match (last_column, last_row) {
+        (true, true) => "\u{251b}",
+        (true, false) => "\u{252b}",
+        (false, true) => "\u{253b}",
+        (false, false) => "\u{254b}",
+    }
+}
+
+/// The top row of a cell, three columns wide: its clue number, if it has one,
+/// and a circle in the corner if it's circled.
+pub fn corner_text(number: Option<u16>, circled: bool) -> String {
+    let number = number.map(|n| n.to_string()).unwrap_or_default();
+
+    if circled && number.len() < 3 {
+        format!("{:<2}\u{25cb}", number)
+    } else {
+        format!("{:<3}", number)
+    }
+}
+
+/// Three columns of text for a guess: the letter with a space on each side, or
+/// the first two letters of a rebus followed by a dot to show there's more.
+/// Compact cells, one column wide, only get the first letter.
+pub fn guess_text(guess: &str, width: usize) -> String {
+    let mut chars = guess.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(a), _) if width == 1 => a.to_string(),
+        (Some(a), None) => format!(" {} ", a),
+        (Some(a), Some(b)) => format!("{}{}\u{b7}", a, b),
+        _ => "   ".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let mut p = puzfile::parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        let grid = render_grid(&p, &RenderOptions::default());
+        let lines: Vec<&str> = grid.lines().collect();
+
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "1  \u{2503}   \u{2503}2  \u{2503}");
+        assert_eq!(lines[1], "   \u{2503}   \u{2503}   \u{2503}");
+        assert_eq!(
+            lines[4],
+            "   \u{2503}\u{2588}\u{2588}\u{2588}\u{2503}   \u{2503}"
+        );
+        assert_eq!(
+            lines[5],
+            "\u{2501}\u{2501}\u{2501}\u{254b}\u{2501}\u{2501}\u{2501}\u{254b}\u{2501}\u{2501}\u{2501}\u{252b}"
+        );
+        assert_eq!(
+            lines[8],
+            "\u{2501}\u{2501}\u{2501}\u{253b}\u{2501}\u{2501}\u{2501}\u{253b}\u{2501}\u{2501}\u{2501}\u{251b}"
+        );
+
+        // Guesses go in the middle row, or the only one in compact cells.
+
+        p.state = concat!("P--", "-.-", "---").into();
+
+        let grid = render_grid(&p, &RenderOptions::default());
+
+        assert_eq!(
+            grid.lines().nth(1),
+            Some(" P \u{2503}   \u{2503}   \u{2503}")
+        );
+
+        let options = RenderOptions {
+            compact: true,
+            solution: true,
+            hide_numbers: true,
+        };
+        let grid = render_grid(&p, &options);
+
+        assert_eq!(grid.lines().count(), 6);
+        assert_eq!(grid.lines().next(), Some("P\u{2503}U\u{2503}Z\u{2503}"));
+
+        let options = RenderOptions {
+            hide_numbers: true,
+            ..RenderOptions::default()
+        };
+
+        assert!(render_grid(&p, &options).starts_with("   \u{2503}"));
+    }
+
+    #[test]
+    fn placeholder_black() {
+        let p = puzfile::parse(include_bytes!("../assets/test2.puz")).unwrap();
+        let split = puzfile::parse(include_bytes!("../assets/test7.puz")).unwrap();
+
+        let options = RenderOptions {
+            solution: true,
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(render_grid(&split, &options), render_grid(&p, &options));
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+/// Solving state that the .puz format has no place for, kept in a JSON file
+/// next to the puzzle.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Sidecar {
+    /// Checksum of the solution of the puzzle this state belongs to. A sidecar
+    /// left over from a different puzzle with the same file name is ignored.
+    pub checksum: u16,
+    /// Cells that were checked and found to be wrong, in grid order.
+    pub marked_wrong: Vec<bool>,
+    /// Cells whose answers were revealed, in grid order.
+    pub revealed: Vec<bool>,
+    /// How many times the player checked or revealed something, and whether
+    /// they revealed the block pattern of a diagramless puzzle. Sidecars
+    /// written before these were kept count as none.
+    #[serde(default)]
+    pub checks: u32,
+    #[serde(default)]
+    pub reveals: u32,
+    #[serde(default)]
+    pub structure_revealed: bool,
+}
+
+/// The sidecar file for a puzzle lives next to it, e.g. `file.puz.puzterm`.
+pub fn path_for(puzzle: &Path) -> PathBuf {
+    let mut s = puzzle.as_os_str().to_owned();
+    s.push(".puzterm");
+
+    PathBuf::from(s)
+}
+
+impl Sidecar {
+    /// Load the sidecar at `path` if it exists and belongs to the puzzle with
+    /// the given checksum.
+    pub fn load(path: &Path, checksum: u16) -> Option<Sidecar> {
+        let f = File::open(path).ok()?;
+        let s: Sidecar = serde_json::from_reader(f).ok()?;
+
+        if s.checksum == checksum {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let f = File::create(path)?;
+
+        serde_json::to_writer(f, self).map_err(io::Error::from)
+    }
+
+    /// True if there's nothing worth writing to disk.
+    pub fn is_empty(&self) -> bool {
+        self.checks == 0
+            && self.reveals == 0
+            && !self.structure_revealed
+            && !self
+                .marked_wrong
+                .iter()
+                .chain(self.revealed.iter())
+                .any(|&b| b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn round_trip() {
+        let path = env::temp_dir().join("puzterm-sidecar-round-trip.puzterm");
+
+        let s = Sidecar {
+            checksum: 0x1234,
+            marked_wrong: vec![false, true, false],
+            revealed: vec![true, false, false],
+            checks: 2,
+            reveals: 1,
+            structure_revealed: true,
+        };
+        s.save(&path).unwrap();
+
+        assert_eq!(Sidecar::load(&path, 0x1234), Some(s));
+        assert_eq!(Sidecar::load(&path, 0x4321), None);
+
+        // Help that left no marks behind is still worth keeping.
+
+        let s = Sidecar {
+            checksum: 0x1234,
+            checks: 1,
+            ..Sidecar::default()
+        };
+
+        assert!(!s.is_empty());
+        assert!(Sidecar::default().is_empty());
+
+        // Older sidecars without the counts still load.
+
+        fs::write(
+            &path,
+            r#"{"checksum":4660,"marked_wrong":[false],"revealed":[true]}"#,
+        )
+        .unwrap();
+
+        let s = Sidecar::load(&path, 0x1234).unwrap();
+
+        assert_eq!(s.revealed, vec![true]);
+        assert_eq!(s.checks, 0);
+        assert!(!s.structure_revealed);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn path() {
+        assert_eq!(
+            path_for(Path::new("dir/file.puz")),
+            PathBuf::from("dir/file.puz.puzterm")
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use puzfile::{self, PuzFile};
+
+/// Size of a cell, in SVG units.
+const CELL: usize = 36;
+
+/// Draw the grid as an SVG image, with either the answers or the letters
+/// filled in so far. The letters of a diagramless puzzle are drawn without
+/// its numbers, and with only the squares the player made black.
+pub fn render(p: &PuzFile, solution: bool) -> String {
+    let width = p.width as usize;
+    let height = p.height as usize;
+    let hide_blocks = !solution && p.is_diagramless();
+    let numbers = if hide_blocks {
+        Vec::new()
+    } else {
+        p.cell_numbers()
+    };
+
+    let mut s = format!(
+        concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" ",
+            "viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\">\n",
+            "<rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n"
+        ),
+        w = width * CELL + 2,
+        h = height * CELL + 2
+    );
+
+    for (i, (truth, guess)) in p.puzzle.chars().zip(p.state.chars()).enumerate() {
+        let x = (i % width) * CELL + 1;
+        let y = (i / width) * CELL + 1;
+
+        let black = if hide_blocks {
+            guess == '.'
+        } else {
+            puzfile::is_black(truth)
+        };
+        let fill = if black { "black" } else { "white" };
+
+        s.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"black\"/>\n",
+            x, y, CELL, CELL, fill
+        ));
+
+        if black {
+            continue;
+        }
+
+        if let Some(Some(n)) = numbers.get(i) {
+            s.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"10\">{}</text>\n",
+                x + 2,
+                y + 10,
+                n
+            ));
+        }
+
+        let letters = match (solution, p.rebus.get(i)) {
+            (true, Some(Some(rebus))) => rebus.clone(),
+            (true, _) => truth.to_string(),
+            (false, _) if guess == '-' => continue,
+            (false, _) => guess.to_string(),
+        };
+
+        // Shrink rebus answers so they fit in the cell.
+
+        let size = 22 / letters.chars().count().clamp(1, 4) + 4;
+
+        s.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            x + CELL / 2,
+            y + CELL - 8,
+            size,
+            escape(&letters)
+        ));
+    }
+
+    s.push_str("</svg>\n");
+    s
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let mut p = puzfile::parse(include_bytes!("../assets/test2.puz")).unwrap();
+
+        let svg = render(&p, true);
+
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("fill=\"black\"").count(), 1);
+        assert!(svg.contains(">1</text>"));
+        assert!(svg.contains(">3</text>"));
+        assert!(svg.contains(">Z</text>"));
+
+        // Only letters that have been guessed show up otherwise.
+
+        p.state = concat!("P--", "-.-", "---").into();
+
+        let svg = render(&p, false);
+
+        assert!(svg.contains(">P</text>"));
+        assert!(!svg.contains(">Z</text>"));
+
+        // A diagramless puzzle only shows the blocks the player marked.
+
+        p.unknown_bitmask = 0x0401;
+        p.state = concat!("P--", "---", "--.").into();
+
+        let svg = render(&p, false);
+
+        assert_eq!(svg.matches("fill=\"black\"").count(), 1);
+        assert!(svg.contains("x=\"73\" y=\"73\" width=\"36\" height=\"36\" fill=\"black\""));
+        assert!(!svg.contains(">1</text>"));
+
+        // The solution shows them all anyway.
+
+        assert!(render(&p, true)
+            .contains("x=\"37\" y=\"37\" width=\"36\" height=\"36\" fill=\"black\""));
+    }
+}
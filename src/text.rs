@@ -0,0 +1,106 @@
+use unicode_width::UnicodeWidthChar;
+
+/// Split off the escape sequence at the start of `s`, if there is one. Only
+/// the kinds termion writes are recognized: `ESC [ ... letter` for styles and
+/// cursor movement, and `ESC ] ... BEL` for the window title.
+fn escape_len(s: &str) -> Option<usize> {
+    let rest = s.strip_prefix('\x1b')?;
+
+    let end = match rest.chars().next()? {
+        '[' => rest[1..].find(|c: char| ('@'..='~').contains(&c))? + 2,
+        ']' => rest.find('\x07')? + 1,
+        c => c.len_utf8(),
+    };
+
+    Some(end + 1)
+}
+
+/// `s` without control characters, so text from a puzzle file can't move
+/// the cursor or start escape sequences. Line breaks are kept.
+pub fn strip_controls(s: &str) -> String {
+    s.chars()
+        .filter(|&c| c == '\n' || !c.is_control())
+        .collect()
+}
+
+/// How many terminal columns `s` takes up. Escape codes take none, and wide
+/// characters like CJK take two.
+pub fn width(s: &str) -> usize {
+    let mut width = 0;
+    let mut i = 0;
+
+    while i < s.len() {
+        if let Some(len) = escape_len(&s[i..]) {
+            i += len;
+            continue;
+        }
+
+        let c = s[i..].chars().next().unwrap();
+        width += c.width().unwrap_or(0);
+        i += c.len_utf8();
+    }
+
+    width
+}
+
+/// The start of `s` that fits in `width` columns. Escape codes are kept, so
+/// styles still get reset after the cut.
+pub fn truncate(s: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    let mut full = false;
+    let mut i = 0;
+
+    while i < s.len() {
+        if let Some(len) = escape_len(&s[i..]) {
+            out.push_str(&s[i..i + len]);
+            i += len;
+            continue;
+        }
+
+        let c = s[i..].chars().next().unwrap();
+        let w = c.width().unwrap_or(0);
+
+        full = full || used + w > width;
+
+        if !full {
+            out.push(c);
+            used += w;
+        }
+
+        i += c.len_utf8();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths() {
+        assert_eq!(width("Été"), 3);
+        assert_eq!(width("\x1b[1mAcross\x1b[m"), 6);
+        assert_eq!(width("漢字"), 4);
+
+        assert_eq!(truncate("Été à Paris", 5), "Été à");
+        assert_eq!(truncate("\x1b[1mAcross\x1b[m", 3), "\x1b[1mAcr\x1b[m");
+        assert_eq!(truncate("漢字a", 3), "漢");
+        assert_eq!(truncate("short", 10), "short");
+
+        // An escape before a character longer than a byte skips all of it.
+
+        assert_eq!(width("abc\x1b\u{e9}def"), 6);
+        assert_eq!(truncate("abc\x1b\u{e9}def", 4), "abc\x1b\u{e9}d");
+    }
+
+    #[test]
+    fn controls() {
+        assert_eq!(strip_controls("abc\x1b[2Jdef"), "abc[2Jdef");
+        assert_eq!(
+            strip_controls("Notepad 1\r\nNotepad 2"),
+            "Notepad 1\nNotepad 2"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::convert::TryFrom;
+use std::fs;
+
+use serde::Deserialize;
+use termion::color;
+use toml;
+
+/// A terminal color, written in a theme file as a name like `"light_red"`,
+/// an ANSI color number like `"237"`, a hex RGB value like `"#ff8800"`, or
+/// `"default"` for whatever the terminal normally uses.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Color {
+    Default,
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Color, String> {
+        let s = s.trim().to_lowercase();
+
+        if s == "default" {
+            return Ok(Color::Default);
+        }
+
+        if let Ok(n) = s.parse() {
+            return Ok(Color::Ansi(n));
+        }
+
+        if s.starts_with('#') && s.len() == 7 {
+            let channel = |i| u8::from_str_radix(&s[i..i + 2], 16);
+
+            if let (Ok(r), Ok(g), Ok(b)) = (channel(1), channel(3), channel(5)) {
+                return Ok(Color::Rgb(r, g, b));
+            }
+        }
+
+        let (light, name) = match s.strip_prefix("light_") {
+            Some(name) => (true, name),
+            None => (false, &s[..]),
+        };
+
+        match NAMES.iter().position(|&n| n == name) {
+            Some(i) if light => Ok(Color::Ansi(i as u8 + 8)),
+            Some(i) => Ok(Color::Ansi(i as u8)),
+            None => Err(format!("unknown color: {:?}", s)),
+        }
+    }
+}
+
+impl Color {
+    /// The escape code for using this as the foreground color. The first 16
+    /// colors use the basic codes, so they work in terminals with only 8 or
+    /// 16 colors too.
+    pub fn fg(self) -> String {
+        match self {
+            Color::Default => String::new(),
+            Color::Ansi(n) if n < 8 => format!("\x1b[{}m", 30 + n),
+            Color::Ansi(n) if n < 16 => format!("\x1b[{}m", 90 + n - 8),
+            Color::Ansi(n) => color::Fg(color::AnsiValue(n)).to_string(),
+            Color::Rgb(r, g, b) => color::Fg(color::Rgb(r, g, b)).to_string(),
+        }
+    }
+
+    /// The escape code for using this as the background color.
+    pub fn bg(self) -> String {
+        match self {
+            Color::Default => String::new(),
+            Color::Ansi(n) if n < 8 => format!("\x1b[{}m", 40 + n),
+            Color::Ansi(n) if n < 16 => format!("\x1b[{}m", 100 + n - 8),
+            Color::Ansi(n) => color::Bg(color::AnsiValue(n)).to_string(),
+            Color::Rgb(r, g, b) => color::Bg(color::Rgb(r, g, b)).to_string(),
+        }
+    }
+}
+
+/// Colors to draw the game in, read from the file given with `--theme`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Theme {
+    /// Behind the letters of white cells.
+    pub background: Color,
+    /// The lines between cells.
+    pub grid: Color,
+    /// The player's letters.
+    pub guess: Color,
+    /// Clue numbers in the corners of cells.
+    pub clue_number: Color,
+    /// The arrow showing which way letters are entered.
+    pub cursor: Color,
+    /// Behind the letters of the word being edited.
+    pub word: Color,
+    /// Behind other cells with the same letter as the cursor cell, when those
+    /// are shown.
+    pub same_letter: Color,
+    pub status_fg: Color,
+    pub status_bg: Color,
+    /// Letters that were checked and found to be wrong.
+    pub error: Color,
+    /// Behind a letter that was checked and found to be right.
+    pub correct: Color,
+    /// Behind a letter that was checked and found to be wrong.
+    pub incorrect: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            background: Color::Default,
+            grid: Color::Default,
+            guess: Color::Ansi(6),
+            clue_number: Color::Default,
+            cursor: Color::Ansi(9),
+            word: Color::Ansi(237),
+            same_letter: Color::Ansi(236),
+            status_fg: Color::Ansi(0),
+            status_bg: Color::Ansi(7),
+            error: Color::Ansi(9),
+            correct: Color::Ansi(2),
+            incorrect: Color::Ansi(1),
+        }
+    }
+}
+
+impl Theme {
+    pub fn load(path: &str) -> Result<Theme, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        Theme::parse(&s).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(s: &str) -> Result<Theme, toml::de::Error> {
+        toml::from_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        let t = Theme::parse(
+            "guess = \"light_blue\"\nword = \"240\"\ngrid = \"#102030\"\nerror = \"default\"",
+        )
+        .unwrap();
+
+        assert_eq!(t.guess, Color::Ansi(12));
+        assert_eq!(t.word, Color::Ansi(240));
+        assert_eq!(t.grid, Color::Rgb(0x10, 0x20, 0x30));
+        assert_eq!(t.error, Color::Default);
+        assert_eq!(t.cursor, Theme::default().cursor);
+
+        assert!(Theme::parse("guess = \"plaid\"").is_err());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(Color::Ansi(6).fg(), "\x1b[36m");
+        assert_eq!(Color::Ansi(9).fg(), "\x1b[91m");
+        assert_eq!(Color::Ansi(0).bg(), "\x1b[40m");
+        assert_eq!(Color::Ansi(15).bg(), "\x1b[107m");
+        assert_eq!(Color::Ansi(237).bg(), "\x1b[48;5;237m");
+        assert_eq!(Color::Default.fg(), "");
+    }
+}